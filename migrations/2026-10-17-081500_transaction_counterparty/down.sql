@@ -0,0 +1,30 @@
+-- SQLite can't drop a column with a foreign key, so the table is rebuilt.
+-- The view has to go while the table is renamed.
+DROP VIEW balances;
+
+CREATE TABLE transactions_new (
+    id INTEGER PRIMARY KEY NOT NULL,
+    amount INTEGER NOT NULL,
+    timestamp TEXT NOT NULL,
+    user TEXT NOT NULL,
+    product_name TEXT,
+
+    FOREIGN KEY(user) REFERENCES users(id)
+);
+
+INSERT INTO transactions_new (id, amount, timestamp, user, product_name)
+SELECT id, amount, timestamp, user, product_name
+FROM transactions;
+
+DROP TABLE transactions;
+
+ALTER TABLE transactions_new RENAME TO transactions;
+
+CREATE VIEW balances AS
+SELECT users.id user_id,
+       users.name,
+       SUM(transactions.amount) amount
+FROM transactions,
+     users
+WHERE users.id == transactions.user
+GROUP BY user_id;
@@ -0,0 +1 @@
+ALTER TABLE transactions ADD COLUMN counterparty TEXT REFERENCES users(id);
@@ -0,0 +1 @@
+ALTER TABLE products ADD COLUMN available BOOLEAN NOT NULL DEFAULT 1;
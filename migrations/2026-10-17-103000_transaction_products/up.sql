@@ -0,0 +1,26 @@
+ALTER TABLE transactions ADD COLUMN product_identifier TEXT REFERENCES products(identifier);
+ALTER TABLE transactions ADD COLUMN product_price INTEGER;
+ALTER TABLE transactions ADD COLUMN quantity INTEGER;
+
+UPDATE transactions
+SET product_identifier = (SELECT identifier
+                          FROM products
+                          WHERE products.name = transactions.product_name
+                          ORDER BY available DESC
+                          LIMIT 1)
+WHERE product_name IS NOT NULL;
+
+-- Quantities were not recorded before, assume the current price if it adds up.
+-- Purchases at another price are left without quantity and price.
+UPDATE transactions
+SET quantity = (SELECT -transactions.amount / products.price
+                FROM products
+                WHERE products.identifier = transactions.product_identifier
+                  AND products.price > 0
+                  AND -transactions.amount > 0
+                  AND -transactions.amount % products.price == 0)
+WHERE product_identifier IS NOT NULL;
+
+UPDATE transactions
+SET product_price = -amount / quantity
+WHERE quantity IS NOT NULL;
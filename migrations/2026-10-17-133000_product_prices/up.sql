@@ -0,0 +1,14 @@
+CREATE TABLE product_prices (
+    id INTEGER PRIMARY KEY NOT NULL,
+    product_identifier TEXT NOT NULL,
+    price INTEGER NOT NULL,
+    valid_from TEXT NOT NULL,
+    valid_until TEXT,
+
+    FOREIGN KEY(product_identifier) REFERENCES products(identifier)
+);
+
+-- The current prices are assumed to have been valid from the start
+INSERT INTO product_prices (product_identifier, price, valid_from)
+SELECT identifier, price, '1970-01-01 00:00:00'
+FROM products;
@@ -0,0 +1,3 @@
+DROP INDEX transactions_import_hash;
+
+ALTER TABLE transactions DROP COLUMN import_hash;
@@ -0,0 +1,3 @@
+ALTER TABLE transactions ADD COLUMN import_hash TEXT;
+
+CREATE UNIQUE INDEX transactions_import_hash ON transactions (import_hash);
@@ -0,0 +1,10 @@
+DROP VIEW balances;
+
+-- Users without transactions have a balance of zero
+CREATE VIEW balances AS
+SELECT users.id user_id,
+       users.name,
+       COALESCE(SUM(transactions.amount), 0) amount
+FROM users
+         LEFT JOIN transactions ON transactions.user = users.id
+GROUP BY users.id;
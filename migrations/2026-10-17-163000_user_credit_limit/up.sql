@@ -0,0 +1 @@
+ALTER TABLE users ADD COLUMN credit_limit INTEGER;
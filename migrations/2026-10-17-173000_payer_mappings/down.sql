@@ -0,0 +1 @@
+DROP TABLE payer_mappings;
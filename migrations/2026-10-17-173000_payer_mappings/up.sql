@@ -0,0 +1,7 @@
+-- Payers are either normalized IBANs or lowercase names
+CREATE TABLE payer_mappings (
+    payer TEXT PRIMARY KEY NOT NULL,
+    user_id TEXT NOT NULL,
+
+    FOREIGN KEY(user_id) REFERENCES users(id)
+);
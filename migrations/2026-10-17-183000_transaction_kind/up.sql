@@ -0,0 +1,5 @@
+ALTER TABLE transactions ADD COLUMN kind TEXT NOT NULL DEFAULT 'deposit';
+
+-- Existing transactions are classified by what they are linked to
+UPDATE transactions SET kind = 'purchase' WHERE product_name IS NOT NULL OR amount < 0;
+UPDATE transactions SET kind = 'transfer' WHERE counterparty IS NOT NULL;
@@ -0,0 +1,9 @@
+-- Supplies paid from the kitty's cash, each reimbursed to the buyer by a transaction
+CREATE TABLE expenses (
+    id INTEGER PRIMARY KEY NOT NULL,
+    transaction_id INTEGER NOT NULL,
+    amount INTEGER NOT NULL,
+    description TEXT NOT NULL,
+
+    FOREIGN KEY(transaction_id) REFERENCES transactions(id)
+);
@@ -0,0 +1,551 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use diesel::SqliteConnection;
+use diesel::{Connection, ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl};
+use roxmltree::{Document, Node};
+
+use crate::error::Error;
+use crate::import::hash_fields;
+use crate::models::{NewTransaction, Rappen, User};
+use crate::qr_bill::Reference;
+use crate::schema::{payer_mappings, transactions, users};
+
+sql_function!(fn upper(x: diesel::sql_types::Text) -> diesel::sql_types::Text);
+
+/// A credit entry of a bank statement.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BankEntry {
+    pub amount: Rappen,
+    pub currency: String,
+    pub booking_date: NaiveDateTime,
+    /// QR or creditor reference of the payment.
+    pub reference: Option<String>,
+    pub debtor_name: Option<String>,
+    pub debtor_iban: Option<String>,
+    /// Unstructured message of the payment.
+    pub message: Option<String>,
+    /// Unique id assigned by the bank.
+    pub bank_reference: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BookedDeposit {
+    pub entry: BankEntry,
+    pub user: User,
+}
+
+/// The outcome of a bank statement import.
+#[derive(Default, Debug, PartialEq)]
+pub struct BankImportReport {
+    pub booked: Vec<BookedDeposit>,
+    /// Entries that were already imported before.
+    pub duplicates: Vec<BankEntry>,
+    /// Entries that couldn't be assigned to a user and need to be booked manually.
+    pub unmatched: Vec<BankEntry>,
+}
+
+impl fmt::Display for BankImportReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} booked, {} duplicates, {} unmatched",
+            self.booked.len(),
+            self.duplicates.len(),
+            self.unmatched.len()
+        )
+    }
+}
+
+/// Books the credit entries of ISO 20022 camt.053 and camt.054 files as deposits.
+///
+/// Entries are assigned to users by the reference of their QR-bill, or by the IBAN
+/// or name of the payer if there is a payer mapping for it.
+pub struct BankStatementImporter<'a> {
+    database_connection: &'a SqliteConnection,
+}
+
+impl<'a> BankStatementImporter<'a> {
+    pub fn new(database_connection: &'a SqliteConnection) -> Self {
+        Self {
+            database_connection,
+        }
+    }
+
+    /// Imports all matched entries in a single database transaction.
+    pub fn import_bank_statement(&self, xml: &str) -> Result<BankImportReport, Error> {
+        let entries = parse_bank_statement(xml)?;
+
+        self.database_connection.transaction(|| {
+            let mut report = BankImportReport::default();
+            let mut seen_hashes = HashSet::new();
+
+            for entry in entries {
+                let user = match self.find_user(&entry)? {
+                    Some(user) if entry.currency == "CHF" => user,
+                    _ => {
+                        report.unmatched.push(entry);
+                        continue;
+                    }
+                };
+
+                let import_hash = hash_entry(&entry);
+                if !seen_hashes.insert(import_hash.clone())
+                    || self.is_already_imported(&import_hash)?
+                {
+                    report.duplicates.push(entry);
+                    continue;
+                }
+
+                diesel::insert_into(transactions::table)
+                    .values(&NewTransaction {
+                        timestamp: entry.booking_date,
+                        import_hash: Some(import_hash),
+                        ..NewTransaction::new(entry.amount, &user)
+                    })
+                    .execute(self.database_connection)
+                    .map_err(Error::Database)?;
+
+                report.booked.push(BookedDeposit { entry, user });
+            }
+
+            Ok(report)
+        })
+    }
+
+    /// Assigns payments from an IBAN or payer name to a user.
+    pub fn add_payer_mapping(&self, payer: &str, user_id: &str) -> Result<(), Error> {
+        users::table
+            .find(user_id)
+            .select(users::id)
+            .first::<String>(self.database_connection)
+            .optional()
+            .map_err(Error::Database)?
+            .ok_or_else(|| Error::NotFound(format!("User \"{}\"", user_id)))?;
+
+        diesel::replace_into(payer_mappings::table)
+            .values((
+                payer_mappings::payer.eq(normalize_payer(payer)),
+                payer_mappings::user_id.eq(user_id),
+            ))
+            .execute(self.database_connection)
+            .map(|_| ())
+            .map_err(Error::Database)
+    }
+
+    fn find_user(&self, entry: &BankEntry) -> Result<Option<User>, Error> {
+        if let Some(reference) = entry.reference.as_deref().and_then(Reference::parse) {
+            let user = users::table
+                .filter(upper(users::id).eq(reference.user_id()))
+                .select((users::id, users::name))
+                .first::<User>(self.database_connection)
+                .optional()
+                .map_err(Error::Database)?;
+
+            if user.is_some() {
+                return Ok(user);
+            }
+        }
+
+        for payer in entry.debtor_iban.iter().chain(entry.debtor_name.iter()) {
+            let user = payer_mappings::table
+                .inner_join(users::table)
+                .filter(payer_mappings::payer.eq(normalize_payer(payer)))
+                .select((users::id, users::name))
+                .first::<User>(self.database_connection)
+                .optional()
+                .map_err(Error::Database)?;
+
+            if user.is_some() {
+                return Ok(user);
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn is_already_imported(&self, import_hash: &str) -> Result<bool, Error> {
+        transactions::table
+            .filter(transactions::import_hash.eq(import_hash))
+            .select(transactions::id)
+            .first::<i32>(self.database_connection)
+            .optional()
+            .map(|transaction| transaction.is_some())
+            .map_err(Error::Database)
+    }
+}
+
+/// Reads the booked credit entries of a camt.053 or camt.054 file.
+/// Entries with several transactions are split into one entry per transaction.
+pub fn parse_bank_statement(xml: &str) -> Result<Vec<BankEntry>, Error> {
+    let document = Document::parse(xml).map_err(|error| Error::Parse(error.to_string()))?;
+
+    let mut entries = Vec::new();
+    for entry in document
+        .descendants()
+        .filter(|node| node.has_tag_name("Ntry"))
+    {
+        let is_credit = text(entry, &["CdtDbtInd"]) == Some("CRDT");
+        // The status is a code element since camt.053.001.08
+        let status = text(entry, &["Sts"]).or_else(|| text(entry, &["Sts", "Cd"]));
+        if !is_credit || status.is_some_and(|status| status != "BOOK") {
+            continue;
+        }
+
+        let booking_date = text(entry, &["BookgDt", "Dt"])
+            .or_else(|| text(entry, &["BookgDt", "DtTm"]))
+            .or_else(|| text(entry, &["ValDt", "Dt"]))
+            .ok_or_else(|| Error::Parse("Entry without booking date".to_string()))
+            .and_then(parse_date)?;
+        let (entry_amount, entry_currency) = parse_amount(entry)?;
+        let entry_reference = text(entry, &["AcctSvcrRef"]);
+
+        let details = entry
+            .children()
+            .filter(|node| node.has_tag_name("NtryDtls"))
+            .flat_map(|node| node.children().filter(|node| node.has_tag_name("TxDtls")))
+            .collect::<Vec<_>>();
+
+        if details.is_empty() {
+            entries.push(BankEntry {
+                amount: entry_amount,
+                currency: entry_currency,
+                booking_date,
+                reference: None,
+                debtor_name: None,
+                debtor_iban: None,
+                message: None,
+                bank_reference: entry_reference.map(str::to_string),
+            });
+            continue;
+        }
+
+        for (index, transaction) in details.iter().enumerate() {
+            let (amount, currency) = match find(*transaction, &["Amt"])
+                .or_else(|| find(*transaction, &["AmtDtls", "TxAmt", "Amt"]))
+            {
+                Some(amount) => parse_amount_node(amount)?,
+                None if details.len() == 1 => (entry_amount, entry_currency.clone()),
+                None => return Err(Error::Parse("Transaction without amount".to_string())),
+            };
+
+            let bank_reference = text(*transaction, &["Refs", "AcctSvcrRef"])
+                .map(str::to_string)
+                .or_else(|| {
+                    entry_reference.map(|reference| match details.len() {
+                        1 => reference.to_string(),
+                        _ => format!("{}/{}", reference, index + 1),
+                    })
+                });
+
+            entries.push(BankEntry {
+                amount,
+                currency,
+                booking_date,
+                reference: text(*transaction, &["RmtInf", "Strd", "CdtrRefInf", "Ref"])
+                    .map(str::to_string),
+                debtor_name: text(*transaction, &["RltdPties", "Dbtr", "Nm"])
+                    .or_else(|| text(*transaction, &["RltdPties", "Dbtr", "Pty", "Nm"]))
+                    .map(str::to_string),
+                debtor_iban: text(*transaction, &["RltdPties", "DbtrAcct", "Id", "IBAN"])
+                    .map(str::to_string),
+                message: text(*transaction, &["RmtInf", "Ustrd"]).map(str::to_string),
+                bank_reference,
+            });
+        }
+    }
+
+    Ok(entries)
+}
+
+fn find<'a, 'input>(node: Node<'a, 'input>, path: &[&str]) -> Option<Node<'a, 'input>> {
+    path.iter().try_fold(node, |node, name| {
+        node.children().find(|child| child.has_tag_name(*name))
+    })
+}
+
+fn text<'a>(node: Node<'a, '_>, path: &[&str]) -> Option<&'a str> {
+    find(node, path)?
+        .text()
+        .map(str::trim)
+        .filter(|text| !text.is_empty())
+}
+
+fn parse_amount(entry: Node) -> Result<(Rappen, String), Error> {
+    find(entry, &["Amt"])
+        .ok_or_else(|| Error::Parse("Entry without amount".to_string()))
+        .and_then(parse_amount_node)
+}
+
+fn parse_amount_node(node: Node) -> Result<(Rappen, String), Error> {
+    let text = node.text().unwrap_or_default().trim();
+    let (francs, rappen) = text.split_once('.').unwrap_or((text, "0"));
+
+    let amount = match (francs.parse::<Rappen>(), rappen.len()) {
+        (Ok(francs), 1..=2) => format!("{:0<2}", rappen)
+            .parse::<Rappen>()
+            .ok()
+            .and_then(|rappen| francs.checked_mul(100)?.checked_add(rappen)),
+        _ => None,
+    }
+    .ok_or_else(|| Error::Parse(text.to_string()))?;
+
+    Ok((amount, node.attribute("Ccy").unwrap_or("CHF").to_string()))
+}
+
+/// Accepts dates and ISO-8601 timestamps, which are converted to UTC.
+fn parse_date(text: &str) -> Result<NaiveDateTime, Error> {
+    DateTime::parse_from_rfc3339(text)
+        .map(|timestamp| timestamp.naive_utc())
+        .or_else(|_| NaiveDateTime::parse_from_str(text, "%Y-%m-%dT%H:%M:%S"))
+        .ok()
+        .or_else(|| {
+            NaiveDate::parse_from_str(text, "%Y-%m-%d")
+                .ok()
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+        })
+        .ok_or_else(|| Error::Parse(text.to_string()))
+}
+
+/// IBANs without whitespace in uppercase, names in lowercase with single spaces.
+fn normalize_payer(payer: &str) -> String {
+    let words = payer.split_whitespace().collect::<Vec<_>>();
+    let compact = words.concat().to_uppercase();
+
+    let is_iban = (15..=34).contains(&compact.len())
+        && compact.chars().take(2).all(|c| c.is_ascii_alphabetic())
+        && compact.chars().skip(2).take(2).all(|c| c.is_ascii_digit())
+        && compact.chars().all(|c| c.is_ascii_alphanumeric());
+
+    if is_iban {
+        compact
+    } else {
+        words.join(" ").to_lowercase()
+    }
+}
+
+/// The bank reference identifies an entry; without one, all fields are used.
+fn hash_entry(entry: &BankEntry) -> String {
+    match &entry.bank_reference {
+        Some(bank_reference) => hash_fields(&["camt", bank_reference]),
+        None => hash_fields(&[
+            "camt",
+            &entry.booking_date.format("%Y-%m-%dT%H:%M:%S").to_string(),
+            &entry.amount.to_string(),
+            &entry.currency,
+            entry.reference.as_deref().unwrap_or_default(),
+            entry.debtor_name.as_deref().unwrap_or_default(),
+            entry.debtor_iban.as_deref().unwrap_or_default(),
+            entry.message.as_deref().unwrap_or_default(),
+        ]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::Transaction;
+    use crate::test_utils::*;
+
+    use super::*;
+
+    const CAMT_053: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Document xmlns="urn:iso:std:iso:20022:tech:xsd:camt.053.001.04">
+  <BkToCstmrStmt>
+    <Stmt>
+      <Ntry>
+        <Amt Ccy="CHF">12.50</Amt>
+        <CdtDbtInd>CRDT</CdtDbtInd>
+        <Sts>BOOK</Sts>
+        <BookgDt><Dt>2026-10-16</Dt></BookgDt>
+        <AcctSvcrRef>ENTRY-1</AcctSvcrRef>
+        <NtryDtls>
+          <TxDtls>
+            <RltdPties><Dbtr><Nm>Anna Muster</Nm></Dbtr></RltdPties>
+            <RmtInf>
+              <Strd><CdtrRefInf><Ref>000000000000000000000012347</Ref></CdtrRefInf></Strd>
+            </RmtInf>
+          </TxDtls>
+        </NtryDtls>
+      </Ntry>
+      <Ntry>
+        <Amt Ccy="CHF">50</Amt>
+        <CdtDbtInd>CRDT</CdtDbtInd>
+        <Sts>BOOK</Sts>
+        <BookgDt><Dt>2026-10-16</Dt></BookgDt>
+        <AcctSvcrRef>ENTRY-2</AcctSvcrRef>
+        <NtryDtls>
+          <TxDtls>
+            <AmtDtls><TxAmt><Amt Ccy="CHF">30.00</Amt></TxAmt></AmtDtls>
+            <RltdPties>
+              <Dbtr><Nm>Ben Beispiel</Nm></Dbtr>
+              <DbtrAcct><Id><IBAN>CH9300762011623852957</IBAN></Id></DbtrAcct>
+            </RltdPties>
+            <RmtInf><Ustrd>Kafi</Ustrd></RmtInf>
+          </TxDtls>
+          <TxDtls>
+            <AmtDtls><TxAmt><Amt Ccy="CHF">20.00</Amt></TxAmt></AmtDtls>
+            <RltdPties><Dbtr><Nm>Max Mustermann</Nm></Dbtr></RltdPties>
+          </TxDtls>
+        </NtryDtls>
+      </Ntry>
+      <Ntry>
+        <Amt Ccy="CHF">100.00</Amt>
+        <CdtDbtInd>DBIT</CdtDbtInd>
+        <Sts>BOOK</Sts>
+        <BookgDt><Dt>2026-10-16</Dt></BookgDt>
+      </Ntry>
+    </Stmt>
+  </BkToCstmrStmt>
+</Document>"#;
+
+    fn setup_database() -> SqliteConnection {
+        let database_connection = setup_in_memory_database();
+
+        diesel::insert_into(users::table)
+            .values(&vec![
+                User {
+                    id: "1234".to_string(),
+                    name: "Anna".to_string(),
+                },
+                User {
+                    id: "5678".to_string(),
+                    name: "Ben".to_string(),
+                },
+            ])
+            .execute(&database_connection)
+            .unwrap();
+
+        database_connection
+    }
+
+    #[test]
+    fn parses_camt_053() {
+        let entries = parse_bank_statement(CAMT_053).unwrap();
+
+        assert_eq!(
+            vec![1250, 3000, 2000],
+            entries.iter().map(|entry| entry.amount).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            Some("000000000000000000000012347".to_string()),
+            entries[0].reference
+        );
+        assert_eq!(Some("ENTRY-1".to_string()), entries[0].bank_reference);
+        assert_eq!(
+            Some("CH9300762011623852957".to_string()),
+            entries[1].debtor_iban
+        );
+        assert_eq!(Some("Kafi".to_string()), entries[1].message);
+        assert_eq!(Some("ENTRY-2/2".to_string()), entries[2].bank_reference);
+    }
+
+    #[test]
+    fn parses_camt_054() {
+        let xml = r#"<Document xmlns="urn:iso:std:iso:20022:tech:xsd:camt.054.001.08">
+  <BkToCstmrDbtCdtNtfctn>
+    <Ntfctn>
+      <Ntry>
+        <Amt Ccy="CHF">5.5</Amt>
+        <CdtDbtInd>CRDT</CdtDbtInd>
+        <Sts><Cd>BOOK</Cd></Sts>
+        <BookgDt><DtTm>2026-10-16T10:15:00+02:00</DtTm></BookgDt>
+        <NtryDtls>
+          <TxDtls>
+            <RltdPties><Dbtr><Pty><Nm>Anna Muster</Nm></Pty></Dbtr></RltdPties>
+          </TxDtls>
+        </NtryDtls>
+      </Ntry>
+      <Ntry>
+        <Amt Ccy="CHF">7.00</Amt>
+        <CdtDbtInd>CRDT</CdtDbtInd>
+        <Sts><Cd>PDNG</Cd></Sts>
+        <BookgDt><Dt>2026-10-16</Dt></BookgDt>
+      </Ntry>
+    </Ntfctn>
+  </BkToCstmrDbtCdtNtfctn>
+</Document>"#;
+
+        let entries = parse_bank_statement(xml).unwrap();
+        assert_eq!(1, entries.len());
+        assert_eq!(550, entries[0].amount);
+        assert_eq!(Some("Anna Muster".to_string()), entries[0].debtor_name);
+        assert_eq!(
+            NaiveDate::from_ymd_opt(2026, 10, 16)
+                .unwrap()
+                .and_hms_opt(8, 15, 0)
+                .unwrap(),
+            entries[0].booking_date
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_documents() {
+        assert!(parse_bank_statement("<Document>").is_err());
+        assert!(parse_bank_statement(
+            "<Document><Ntry><Amt>lots</Amt><CdtDbtInd>CRDT</CdtDbtInd>\
+             <BookgDt><Dt>2026-10-16</Dt></BookgDt></Ntry></Document>"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn books_matched_entries() {
+        let database_connection = setup_database();
+        let importer = BankStatementImporter::new(&database_connection);
+        importer
+            .add_payer_mapping("CH93 0076 2011 6238 5295 7", "5678")
+            .unwrap();
+
+        let report = importer.import_bank_statement(CAMT_053).unwrap();
+        assert_eq!(
+            vec![("Anna", 1250), ("Ben", 3000)],
+            report
+                .booked
+                .iter()
+                .map(|deposit| (deposit.user.name.as_str(), deposit.entry.amount))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(1, report.unmatched.len());
+        assert_eq!(
+            Some("Max Mustermann".to_string()),
+            report.unmatched[0].debtor_name
+        );
+
+        importer
+            .add_payer_mapping("max  MUSTERMANN", "5678")
+            .unwrap();
+        let report = importer.import_bank_statement(CAMT_053).unwrap();
+        assert_eq!(1, report.booked.len());
+        assert_eq!(2, report.duplicates.len());
+        assert!(report.unmatched.is_empty());
+
+        let transactions = transactions::table
+            .order(transactions::id)
+            .load::<Transaction>(&database_connection)
+            .unwrap();
+        assert_eq!(3, transactions.len());
+        assert_eq!("1234", transactions[0].user);
+        assert_eq!(None, transactions[0].product_name);
+    }
+
+    #[test]
+    fn payer_mapping_requires_user() {
+        let database_connection = setup_database();
+        let importer = BankStatementImporter::new(&database_connection);
+
+        assert_eq!(
+            Err(Error::NotFound("User \"42\"".to_string())),
+            importer.add_payer_mapping("Anna Muster", "42")
+        );
+    }
+
+    #[test]
+    fn normalizes_payers() {
+        assert_eq!(
+            "CH9300762011623852957",
+            normalize_payer("ch93 0076 2011 6238 5295 7")
+        );
+        assert_eq!("anna muster", normalize_payer(" Anna  Muster "));
+    }
+}
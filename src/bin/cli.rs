@@ -0,0 +1,483 @@
+use std::env;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+use std::process;
+
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use diesel::{Connection, SqliteConnection};
+
+use kafi_kaesseli::bank_import::BankStatementImporter;
+use kafi_kaesseli::config::Config;
+use kafi_kaesseli::currency_handling::currency_formatter::{
+    CurrencyFormatter, CurrencyFormatterImpl,
+};
+use kafi_kaesseli::currency_handling::currency_parser::CurrencyParserImpl;
+use kafi_kaesseli::data_loader::file_data_provider::ProductFileDataProvider;
+use kafi_kaesseli::data_loader::{DataLoader, DataLoaderImpl};
+use kafi_kaesseli::export::{export_ledger, ExportFilter, ExportFormat};
+use kafi_kaesseli::import::LedgerImporter;
+use kafi_kaesseli::message_handler::{MessageHandler, MessageHandlerImpl};
+use kafi_kaesseli::models::{Message, User};
+use kafi_kaesseli::run_migrations;
+use kafi_kaesseli::services::statement_service::StatementServiceImpl;
+use kafi_kaesseli::statement_renderer::render_monthly_statements;
+
+const USAGE: &str = "Usage: kafi-cli <database> [--user-id <id>] [--user-name <name>] \
+                     [--products <file>] [--script <file>]\n       \
+                     kafi-cli <database> --export <file.csv|file.json> \
+                     [--from <YYYY-MM-DD>] [--until <YYYY-MM-DD>] [--export-user <id>]\n       \
+                     kafi-cli <database> --import <ledger.csv>\n       \
+                     kafi-cli <database> --bank-import <camt.xml>\n       \
+                     kafi-cli <database> --map-payer <iban or name>=<user id>\n       \
+                     kafi-cli <database> --statements <YYYY-MM>";
+
+struct Options {
+    database_url: String,
+    user: User,
+    products: Option<String>,
+    script: Option<String>,
+    export: Option<String>,
+    export_filter: ExportFilter,
+    import: Option<String>,
+    bank_import: Option<String>,
+    map_payer: Option<(String, String)>,
+    /// First day of the month to print the statements of all users for.
+    statements: Option<NaiveDate>,
+}
+
+fn parse_options(arguments: &[String]) -> Result<Options, String> {
+    let mut database_url = None;
+    let mut user_id = None;
+    let mut user_name = None;
+    let mut products = None;
+    let mut script = None;
+    let mut export = None;
+    let mut from = None;
+    let mut until = None;
+    let mut export_user = None;
+    let mut import = None;
+    let mut bank_import = None;
+    let mut map_payer = None;
+    let mut statements = None;
+
+    let mut arguments = arguments.iter();
+    while let Some(argument) = arguments.next() {
+        let target = match argument.as_ref() {
+            "--user-id" => &mut user_id,
+            "--user-name" => &mut user_name,
+            "--products" => &mut products,
+            "--script" => &mut script,
+            "--export" => &mut export,
+            "--from" => &mut from,
+            "--until" => &mut until,
+            "--export-user" => &mut export_user,
+            "--import" => &mut import,
+            "--bank-import" => &mut bank_import,
+            "--map-payer" => &mut map_payer,
+            "--statements" => &mut statements,
+            _ if argument.starts_with("--") => {
+                return Err(format!("Unknown option {}", argument));
+            }
+            _ => {
+                if database_url.replace(argument.clone()).is_some() {
+                    return Err("Only one database can be given".to_string());
+                }
+                continue;
+            }
+        };
+
+        let value = arguments
+            .next()
+            .ok_or_else(|| format!("Missing value for {}", argument))?;
+        *target = Some(value.clone());
+    }
+
+    let user_id = user_id.unwrap_or_else(|| "cli".to_string());
+    let user_name = user_name.unwrap_or_else(|| user_id.clone());
+
+    Ok(Options {
+        database_url: database_url.ok_or("Missing database")?,
+        user: User {
+            id: user_id,
+            name: user_name,
+        },
+        products,
+        script,
+        export,
+        export_filter: ExportFilter {
+            from: from.map(|from| parse_date(&from)).transpose()?,
+            // The end date is inclusive
+            until: until
+                .map(|until| parse_date(&until).map(|until| until + Duration::days(1)))
+                .transpose()?,
+            user_id: export_user,
+        },
+        import,
+        bank_import,
+        map_payer: map_payer
+            .map(|mapping| {
+                mapping
+                    .rsplit_once('=')
+                    .map(|(payer, user_id)| (payer.trim().to_string(), user_id.trim().to_string()))
+                    .filter(|(payer, user_id)| !payer.is_empty() && !user_id.is_empty())
+                    .ok_or_else(|| format!("Invalid payer mapping {}", mapping))
+            })
+            .transpose()?,
+        statements: statements
+            .map(|month| {
+                NaiveDate::parse_from_str(&format!("{}-01", month), "%Y-%m-%d")
+                    .map_err(|_| format!("Invalid month {}, expected YYYY-MM", month))
+            })
+            .transpose()?,
+    })
+}
+
+fn parse_date(date: &str) -> Result<NaiveDateTime, String> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map(|date| date.and_hms_opt(0, 0, 0).unwrap())
+        .map_err(|_| format!("Invalid date {}, expected YYYY-MM-DD", date))
+}
+
+fn export(database_connection: &SqliteConnection, path: &str, filter: &ExportFilter) {
+    let format = Path::new(path)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .and_then(ExportFormat::from_extension)
+        .unwrap_or_else(|| fail(format!("Unsupported export file {}", path)));
+    let file = File::create(path)
+        .unwrap_or_else(|error| fail(format!("Unable to create {}: {}", path, error)));
+
+    let count = export_ledger(
+        database_connection,
+        filter,
+        format,
+        &CurrencyFormatterImpl,
+        file,
+    )
+    .unwrap_or_else(|error| fail(error));
+    eprintln!("Exported {} transactions to {}", count, path);
+}
+
+fn import(database_connection: &SqliteConnection, path: &str) {
+    let file = File::open(path)
+        .unwrap_or_else(|error| fail(format!("Unable to open {}: {}", path, error)));
+
+    let report = LedgerImporter::new(database_connection, Box::new(CurrencyParserImpl))
+        .import_ledger(file)
+        .unwrap_or_else(|error| fail(error));
+
+    for error in &report.errors {
+        eprintln!("{}", error);
+    }
+    for line in &report.duplicates {
+        eprintln!("Line {}: skipped duplicate", line);
+    }
+    eprintln!("{}", report);
+
+    if !report.is_successful() {
+        fail("Nothing was imported");
+    }
+}
+
+fn bank_import(database_connection: &SqliteConnection, path: &str) {
+    let xml = std::fs::read_to_string(path)
+        .unwrap_or_else(|error| fail(format!("Unable to open {}: {}", path, error)));
+
+    let report = BankStatementImporter::new(database_connection)
+        .import_bank_statement(&xml)
+        .unwrap_or_else(|error| fail(error));
+
+    let currency_formatter = CurrencyFormatterImpl;
+    for deposit in &report.booked {
+        eprintln!(
+            "{}: booked {} for {}",
+            deposit.entry.booking_date.format("%d.%m.%Y"),
+            currency_formatter.format_amount(deposit.entry.amount),
+            deposit.user.name
+        );
+    }
+    for entry in &report.unmatched {
+        let details = [
+            entry.debtor_name.as_deref(),
+            entry.debtor_iban.as_deref(),
+            entry.reference.as_deref(),
+            entry.message.as_deref(),
+        ]
+        .iter()
+        .flatten()
+        .copied()
+        .collect::<Vec<_>>();
+
+        eprintln!(
+            "{}: unmatched {} {} ({})",
+            entry.booking_date.format("%d.%m.%Y"),
+            currency_formatter.format_amount(entry.amount),
+            entry.currency,
+            details.join(", ")
+        );
+    }
+    eprintln!("{}", report);
+}
+
+/// Feeds every non-empty line through the message handler. Lines starting with `#`
+/// are treated as comments so scripts can be annotated.
+fn run_session(
+    message_handler: &dyn MessageHandler,
+    sender: &User,
+    input: impl BufRead,
+    mut output: impl Write,
+    echo_input: bool,
+) -> io::Result<()> {
+    if !echo_input {
+        write!(output, "> ")?;
+        output.flush()?;
+    }
+
+    for line in input.lines() {
+        let line = line?;
+        let contents = line.trim();
+
+        if !contents.is_empty() && !contents.starts_with('#') {
+            if echo_input {
+                writeln!(output, "> {}", contents)?;
+            }
+
+            let responses = message_handler.handle_message(&Message {
+                sender: sender.clone(),
+                contents: contents.to_string(),
+            });
+
+            for response in responses {
+                writeln!(output, "{}", response.contents)?;
+                if let Some(attachment) = response.attachment {
+                    writeln!(
+                        output,
+                        "[{}, {} bytes]",
+                        attachment.file_name,
+                        attachment.data.len()
+                    )?;
+                }
+            }
+        }
+
+        if !echo_input {
+            write!(output, "> ")?;
+            output.flush()?;
+        }
+    }
+
+    Ok(())
+}
+
+fn fail(message: impl std::fmt::Display) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
+}
+
+fn main() {
+    env_logger::init();
+
+    let arguments = env::args().skip(1).collect::<Vec<_>>();
+    let options =
+        parse_options(&arguments).unwrap_or_else(|error| fail(format!("{}\n{}", error, USAGE)));
+
+    let config = Config::from_env().unwrap_or_else(|error| fail(error));
+
+    let database_connection = SqliteConnection::establish(&options.database_url)
+        .unwrap_or_else(|error| fail(format!("Unable to open database: {}", error)));
+    run_migrations(&database_connection).unwrap_or_else(|error| fail(error));
+
+    if let Some(products) = &options.products {
+        let product_data_provider =
+            ProductFileDataProvider::from_path(products, Box::new(CurrencyParserImpl))
+                .unwrap_or_else(|error| fail(error));
+        let report = DataLoaderImpl::new(&database_connection, Box::new(product_data_provider))
+            .load_product_data()
+            .unwrap_or_else(|error| fail(error));
+        eprintln!("Loaded products: {}", report);
+    }
+
+    if let Some(path) = &options.import {
+        import(&database_connection, path);
+        return;
+    }
+
+    if let Some((payer, user_id)) = &options.map_payer {
+        BankStatementImporter::new(&database_connection)
+            .add_payer_mapping(payer, user_id)
+            .unwrap_or_else(|error| fail(error));
+        eprintln!("Payments from {} are now booked for {}", payer, user_id);
+        return;
+    }
+
+    if let Some(path) = &options.bank_import {
+        bank_import(&database_connection, path);
+        return;
+    }
+
+    if let Some(path) = &options.export {
+        export(&database_connection, path, &options.export_filter);
+        return;
+    }
+
+    if let Some(month) = options.statements {
+        let statements = render_monthly_statements(
+            &StatementServiceImpl::new(&database_connection),
+            month,
+            &CurrencyFormatterImpl,
+        )
+        .unwrap_or_else(|error| fail(error));
+        println!("{}", statements);
+        return;
+    }
+
+    let message_handler =
+        MessageHandlerImpl::with_database_connection(&database_connection, config);
+    let stdout = io::stdout();
+
+    let result = match &options.script {
+        Some(script) => {
+            let file = File::open(script)
+                .unwrap_or_else(|error| fail(format!("Unable to open {}: {}", script, error)));
+            run_session(
+                &message_handler,
+                &options.user,
+                BufReader::new(file),
+                stdout.lock(),
+                true,
+            )
+        }
+        None => run_session(
+            &message_handler,
+            &options.user,
+            io::stdin().lock(),
+            stdout.lock(),
+            false,
+        ),
+    };
+
+    result.unwrap_or_else(|error| fail(error));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_options() {
+        let arguments = ["kafi.sqlite", "--user-id", "42", "--script", "smoke.txt"]
+            .iter()
+            .map(|argument| argument.to_string())
+            .collect::<Vec<_>>();
+
+        let options = parse_options(&arguments).unwrap();
+        assert_eq!("kafi.sqlite", options.database_url);
+        assert_eq!("42", options.user.id);
+        assert_eq!("42", options.user.name);
+        assert_eq!(Some("smoke.txt".to_string()), options.script);
+    }
+
+    #[test]
+    fn parses_export_options() {
+        let arguments = [
+            "kafi.sqlite",
+            "--export",
+            "ledger.csv",
+            "--until",
+            "2026-09-30",
+        ]
+        .iter()
+        .map(|argument| argument.to_string())
+        .collect::<Vec<_>>();
+
+        let options = parse_options(&arguments).unwrap();
+        assert_eq!(Some("ledger.csv".to_string()), options.export);
+        assert_eq!(None, options.export_filter.from);
+        assert_eq!(
+            Some(parse_date("2026-10-01").unwrap()),
+            options.export_filter.until
+        );
+    }
+
+    #[test]
+    fn parses_payer_mapping() {
+        let arguments = ["kafi.sqlite", "--map-payer", "Anna Muster = 1234"]
+            .iter()
+            .map(|argument| argument.to_string())
+            .collect::<Vec<_>>();
+
+        let options = parse_options(&arguments).unwrap();
+        assert_eq!(
+            Some(("Anna Muster".to_string(), "1234".to_string())),
+            options.map_payer
+        );
+
+        let arguments = ["kafi.sqlite", "--map-payer", "Anna Muster"]
+            .iter()
+            .map(|argument| argument.to_string())
+            .collect::<Vec<_>>();
+        assert!(parse_options(&arguments).is_err());
+    }
+
+    #[test]
+    fn parses_statement_month() {
+        let arguments = ["kafi.sqlite", "--statements", "2026-09"]
+            .iter()
+            .map(|argument| argument.to_string())
+            .collect::<Vec<_>>();
+
+        let options = parse_options(&arguments).unwrap();
+        assert_eq!(NaiveDate::from_ymd_opt(2026, 9, 1), options.statements);
+
+        let arguments = ["kafi.sqlite", "--statements", "September"]
+            .iter()
+            .map(|argument| argument.to_string())
+            .collect::<Vec<_>>();
+        assert!(parse_options(&arguments).is_err());
+    }
+
+    #[test]
+    fn requires_database() {
+        assert!(parse_options(&[]).is_err());
+    }
+
+    #[test]
+    fn runs_script_against_database() {
+        let database_connection = SqliteConnection::establish(":memory:").unwrap();
+        run_migrations(&database_connection).unwrap();
+
+        let message_handler =
+            MessageHandlerImpl::with_database_connection(&database_connection, Config::default());
+        let user = User {
+            id: "1".to_string(),
+            name: "Anna".to_string(),
+        };
+
+        let script = "# top up\n10\n\n/history\n";
+        let mut output = Vec::new();
+        run_session(
+            &message_handler,
+            &user,
+            script.as_bytes(),
+            &mut output,
+            true,
+        )
+        .unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        let lines = output.lines().collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                "> 10",
+                "Recorded 10.-",
+                "Current stats:",
+                "*- Anna (10.-)*",
+                "> /history",
+                "Last transactions:",
+            ],
+            lines[..6].to_vec()
+        );
+        assert!(lines[6].ends_with("deposit (10.-)"));
+    }
+}
@@ -0,0 +1,69 @@
+use std::env;
+use std::process;
+
+use diesel::{Connection, SqliteConnection};
+
+use kafi_kaesseli::config::Config;
+use kafi_kaesseli::currency_handling::currency_parser::CurrencyParserImpl;
+use kafi_kaesseli::data_loader::file_data_provider::ProductFileDataProvider;
+use kafi_kaesseli::data_loader::{DataLoader, DataLoaderImpl};
+use kafi_kaesseli::message_handler::MessageHandlerImpl;
+use kafi_kaesseli::run_migrations;
+use kafi_kaesseli::telegram::api::{TelegramApi, DEFAULT_API_URL};
+use kafi_kaesseli::telegram::TelegramBot;
+
+fn required_variable(name: &str) -> String {
+    env::var(name).unwrap_or_else(|_| {
+        eprintln!("Missing environment variable {}", name);
+        process::exit(1);
+    })
+}
+
+fn main() {
+    env_logger::init();
+
+    let token = required_variable("TELEGRAM_BOT_TOKEN");
+    let database_url = required_variable("DATABASE_URL");
+    let api_url = env::var("TELEGRAM_API_URL").unwrap_or_else(|_| DEFAULT_API_URL.to_string());
+
+    let config = Config::from_env().unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        process::exit(1);
+    });
+
+    let database_connection = SqliteConnection::establish(&database_url).unwrap_or_else(|error| {
+        eprintln!("Unable to open database {}: {}", database_url, error);
+        process::exit(1);
+    });
+
+    if let Err(error) = run_migrations(&database_connection) {
+        eprintln!("{}", error);
+        process::exit(1);
+    }
+
+    if let Ok(products) = env::var("PRODUCTS_FILE") {
+        let result = ProductFileDataProvider::from_path(&products, Box::new(CurrencyParserImpl))
+            .and_then(|product_data_provider| {
+                DataLoaderImpl::new(&database_connection, Box::new(product_data_provider))
+                    .load_product_data()
+            });
+
+        match result {
+            Ok(report) => log::info!("Loaded products from {}: {}", products, report),
+            Err(error) => {
+                eprintln!("Unable to load products from {}: {}", products, error);
+                process::exit(1);
+            }
+        }
+    }
+
+    let message_handler =
+        MessageHandlerImpl::with_database_connection(&database_connection, config);
+    let mut bot = TelegramBot::new(
+        TelegramApi::new(&api_url, &token),
+        Box::new(message_handler),
+    );
+
+    log::info!("Polling {} for updates", api_url);
+    bot.run()
+}
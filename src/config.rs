@@ -0,0 +1,231 @@
+use std::env;
+
+use crate::currency_handling::currency_parser::{CurrencyParser, CurrencyParserImpl};
+use crate::error::Error;
+use crate::models::Rappen;
+use crate::qr_bill::{normalize_iban, Creditor};
+
+const ADMINS_VARIABLE: &str = "KAFI_ADMINS";
+const CREDIT_LIMIT_VARIABLE: &str = "KAFI_CREDIT_LIMIT";
+const CREDITOR_IBAN_VARIABLE: &str = "KAFI_CREDITOR_IBAN";
+const CREDITOR_NAME_VARIABLE: &str = "KAFI_CREDITOR_NAME";
+const CREDITOR_STREET_VARIABLE: &str = "KAFI_CREDITOR_STREET";
+const CREDITOR_BUILDING_NUMBER_VARIABLE: &str = "KAFI_CREDITOR_BUILDING_NUMBER";
+const CREDITOR_POSTAL_CODE_VARIABLE: &str = "KAFI_CREDITOR_POSTAL_CODE";
+const CREDITOR_TOWN_VARIABLE: &str = "KAFI_CREDITOR_TOWN";
+const CREDITOR_COUNTRY_VARIABLE: &str = "KAFI_CREDITOR_COUNTRY";
+const LOW_STOCK_THRESHOLD_VARIABLE: &str = "KAFI_LOW_STOCK_THRESHOLD";
+const OUT_OF_STOCK_POLICY_VARIABLE: &str = "KAFI_OUT_OF_STOCK_POLICY";
+
+/// What happens when someone books more units of a product than are in stock.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OutOfStockPolicy {
+    /// Record the transaction and add a warning to the response.
+    Warn,
+    /// Reject the transaction.
+    Refuse,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Config {
+    /// Ids of the users that are always admins, regardless of their stored role.
+    pub admins: Vec<String>,
+    /// Products with at most this many units left are reported as running low.
+    pub low_stock_threshold: i32,
+    pub out_of_stock_policy: OutOfStockPolicy,
+    /// How far balances may go below zero, unless a user has their own limit.
+    pub credit_limit: Option<Rappen>,
+    /// The account top-ups are paid to. QR-bills are only available if it is set.
+    pub creditor: Option<Creditor>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            admins: Vec::new(),
+            low_stock_threshold: 5,
+            out_of_stock_policy: OutOfStockPolicy::Warn,
+            credit_limit: None,
+            creditor: None,
+        }
+    }
+}
+
+impl Config {
+    /// Reads the configuration from `KAFI_*` environment variables,
+    /// using the defaults for variables that are not set.
+    pub fn from_env() -> Result<Self, Error> {
+        Self::from_variables(|name| env::var(name).ok())
+    }
+
+    fn from_variables(variable: impl Fn(&str) -> Option<String>) -> Result<Self, Error> {
+        let mut config = Self::default();
+
+        if let Some(value) = variable(ADMINS_VARIABLE) {
+            config.admins = value
+                .split(',')
+                .map(str::trim)
+                .filter(|id| !id.is_empty())
+                .map(str::to_string)
+                .collect();
+        }
+
+        if let Some(value) = variable(LOW_STOCK_THRESHOLD_VARIABLE) {
+            config.low_stock_threshold = value.trim().parse().map_err(|_| {
+                Error::Validation(format!(
+                    "{} must be a number, got \"{}\"",
+                    LOW_STOCK_THRESHOLD_VARIABLE, value
+                ))
+            })?;
+        }
+
+        if let Some(value) = variable(OUT_OF_STOCK_POLICY_VARIABLE) {
+            config.out_of_stock_policy = match value.trim().to_lowercase().as_ref() {
+                "warn" => OutOfStockPolicy::Warn,
+                "refuse" => OutOfStockPolicy::Refuse,
+                _ => {
+                    return Err(Error::Validation(format!(
+                        "{} must be \"warn\" or \"refuse\", got \"{}\"",
+                        OUT_OF_STOCK_POLICY_VARIABLE, value
+                    )))
+                }
+            };
+        }
+
+        if let Some(value) = variable(CREDIT_LIMIT_VARIABLE) {
+            let credit_limit = CurrencyParserImpl.parse_text(value.trim())?;
+            if credit_limit < 0 {
+                return Err(Error::Validation(format!(
+                    "{} must not be negative",
+                    CREDIT_LIMIT_VARIABLE
+                )));
+            }
+            config.credit_limit = Some(credit_limit);
+        }
+
+        if let Some(iban) = variable(CREDITOR_IBAN_VARIABLE) {
+            let required = |name| {
+                variable(name)
+                    .map(|value| value.trim().to_string())
+                    .filter(|value| !value.is_empty())
+                    .ok_or_else(|| {
+                        Error::Validation(format!(
+                            "{} must be set when {} is set",
+                            name, CREDITOR_IBAN_VARIABLE
+                        ))
+                    })
+            };
+            let optional = |name| {
+                variable(name)
+                    .map(|value: String| value.trim().to_string())
+                    .filter(|value| !value.is_empty())
+            };
+
+            config.creditor = Some(Creditor {
+                iban: normalize_iban(&iban)?,
+                name: required(CREDITOR_NAME_VARIABLE)?,
+                street: optional(CREDITOR_STREET_VARIABLE),
+                building_number: optional(CREDITOR_BUILDING_NUMBER_VARIABLE),
+                postal_code: required(CREDITOR_POSTAL_CODE_VARIABLE)?,
+                town: required(CREDITOR_TOWN_VARIABLE)?,
+                country: optional(CREDITOR_COUNTRY_VARIABLE)
+                    .map(|country| country.to_uppercase())
+                    .unwrap_or_else(|| "CH".to_string()),
+            });
+        }
+
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uses_defaults_for_missing_variables() {
+        assert_eq!(Ok(Config::default()), Config::from_variables(|_| None));
+    }
+
+    #[test]
+    fn reads_stock_settings() {
+        let config = Config::from_variables(|name| match name {
+            LOW_STOCK_THRESHOLD_VARIABLE => Some("3".to_string()),
+            OUT_OF_STOCK_POLICY_VARIABLE => Some("Refuse".to_string()),
+            _ => None,
+        })
+        .unwrap();
+
+        assert_eq!(3, config.low_stock_threshold);
+        assert_eq!(OutOfStockPolicy::Refuse, config.out_of_stock_policy);
+    }
+
+    #[test]
+    fn reads_admin_list() {
+        let config = Config::from_variables(|name| match name {
+            ADMINS_VARIABLE => Some("1234, 5678,".to_string()),
+            _ => None,
+        })
+        .unwrap();
+
+        assert_eq!(vec!["1234".to_string(), "5678".to_string()], config.admins);
+    }
+
+    #[test]
+    fn reads_credit_limit() {
+        let config = Config::from_variables(|name| match name {
+            CREDIT_LIMIT_VARIABLE => Some("50.-".to_string()),
+            _ => None,
+        })
+        .unwrap();
+
+        assert_eq!(Some(5000), config.credit_limit);
+    }
+
+    #[test]
+    fn reads_creditor() {
+        let config = Config::from_variables(|name| match name {
+            CREDITOR_IBAN_VARIABLE => Some("CH93 0076 2011 6238 5295 7".to_string()),
+            CREDITOR_NAME_VARIABLE => Some("Kafi Kässeli".to_string()),
+            CREDITOR_POSTAL_CODE_VARIABLE => Some("8001".to_string()),
+            CREDITOR_TOWN_VARIABLE => Some("Zürich".to_string()),
+            _ => None,
+        })
+        .unwrap();
+
+        assert_eq!(
+            Some(Creditor {
+                iban: "CH9300762011623852957".to_string(),
+                name: "Kafi Kässeli".to_string(),
+                street: None,
+                building_number: None,
+                postal_code: "8001".to_string(),
+                town: "Zürich".to_string(),
+                country: "CH".to_string(),
+            }),
+            config.creditor
+        );
+    }
+
+    #[test]
+    fn creditor_requires_address() {
+        assert_eq!(
+            Err(Error::Validation(
+                "KAFI_CREDITOR_NAME must be set when KAFI_CREDITOR_IBAN is set".to_string()
+            )),
+            Config::from_variables(|name| match name {
+                CREDITOR_IBAN_VARIABLE => Some("CH9300762011623852957".to_string()),
+                _ => None,
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_policy() {
+        Config::from_variables(|name| match name {
+            OUT_OF_STOCK_POLICY_VARIABLE => Some("ignore".to_string()),
+            _ => None,
+        })
+        .unwrap_err();
+    }
+}
@@ -5,10 +5,11 @@ use mockiato::mockable;
 use nom::branch::alt;
 use nom::character::complete::{char as nom_char, digit1};
 use nom::character::is_digit;
-use nom::combinator::{map, opt};
+use nom::combinator::{map, map_res, opt};
 use nom::multi::many0;
 use nom::sequence::tuple;
 
+use crate::error::Error;
 use crate::models::Rappen;
 
 use self::nom::bytes::complete::take_while_m_n;
@@ -17,14 +18,14 @@ use self::nom::sequence::terminated;
 
 #[cfg_attr(test, mockable)]
 pub trait CurrencyParser {
-    fn parse_text(&self, text: &str) -> Result<Rappen, ()>;
+    fn parse_text(&self, text: &str) -> Result<Rappen, Error>;
 }
 
 #[derive(Default)]
 pub struct CurrencyParserImpl;
 
 impl CurrencyParser for CurrencyParserImpl {
-    fn parse_text(&self, text: &str) -> Result<Rappen, ()> {
+    fn parse_text(&self, text: &str) -> Result<Rappen, Error> {
         let separator = alt((nom_char('.'), nom_char(',')));
 
         let sign = map(
@@ -35,7 +36,7 @@ impl CurrencyParser for CurrencyParserImpl {
             |option| option.is_none(),
         );
 
-        let franken_amount = map(digit1, |digits: &str| digits.parse::<u16>().unwrap());
+        let franken_amount = map_res(digit1, |digits: &str| digits.parse::<u16>());
         let rappen_amount = alt((
             map(take_while_m_n(2, 2, is_char_digit), |digits: &str| {
                 digits.parse::<u16>().unwrap()
@@ -66,15 +67,16 @@ impl CurrencyParser for CurrencyParserImpl {
             )),
         ));
 
-        let (remaining_text, parsed_data) = parser(&text).map_err(|_| ())?;
+        let (remaining_text, parsed_data) =
+            parser(text).map_err(|_| Error::Parse(text.to_string()))?;
 
         if !remaining_text.is_empty() {
-            return Err(());
+            return Err(Error::Parse(text.to_string()));
         }
 
         let (is_positive, (franken_amount, rappen_amount)) = parsed_data;
 
-        let value: Rappen = (franken_amount * 100 + rappen_amount).into();
+        let value = Rappen::from(franken_amount) * 100 + Rappen::from(rappen_amount);
         if is_positive {
             Ok(value)
         } else {
@@ -91,7 +93,7 @@ fn is_char_digit(chr: char) -> bool {
 mod tests {
     use super::*;
 
-    fn test_parser(input: &str, expected: Result<Rappen, ()>) {
+    fn test_parser(input: &str, expected: Result<Rappen, Error>) {
         let parser = CurrencyParserImpl::default();
         let actual = parser.parse_text(input);
 
@@ -130,11 +132,21 @@ mod tests {
 
     #[test]
     fn invalid_rappen_amount() {
-        test_parser("2.005", Err(()))
+        test_parser("2.005", Err(Error::Parse("2.005".to_string())))
     }
 
     #[test]
     fn space_after_minus_sign() {
         test_parser("- 1.-", Ok(-100))
     }
+
+    #[test]
+    fn large_franken_amount() {
+        test_parser("1000", Ok(100_000))
+    }
+
+    #[test]
+    fn franken_amount_out_of_range() {
+        test_parser("100000", Err(Error::Parse("100000".to_string())))
+    }
 }
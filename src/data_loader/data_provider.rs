@@ -1,9 +1,10 @@
 #[cfg(test)]
 use mockiato::mockable;
 
+use crate::error::Error;
 use crate::models::Product;
 
 #[cfg_attr(test, mockable)]
 pub trait DataProvider<T> {
-    fn get_data(&self) -> Box<dyn Iterator<Item = Result<T, ()>>>;
+    fn get_data(&self) -> Box<dyn Iterator<Item = Result<T, Error>>>;
 }
@@ -0,0 +1,365 @@
+use std::fs;
+use std::path::PathBuf;
+
+use serde::Deserialize;
+use serde_json::value::RawValue;
+use toml::Spanned;
+
+use crate::currency_handling::currency_parser::CurrencyParser;
+use crate::error::Error;
+use crate::models::Product;
+
+use super::data_provider::DataProvider;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ProductFileFormat {
+    /// A header row followed by `identifier,name,price` rows.
+    Csv,
+    /// An array of tables called `products`.
+    Toml,
+    /// A top level array of objects.
+    Json,
+}
+
+impl ProductFileFormat {
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_lowercase().as_ref() {
+            "csv" => Some(ProductFileFormat::Csv),
+            "toml" => Some(ProductFileFormat::Toml),
+            "json" => Some(ProductFileFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+/// Prices can be written as text (`"4.20"`, `"2.-"`) or as plain numbers.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PriceRecord {
+    Text(String),
+    Number(f64),
+}
+
+#[derive(Deserialize)]
+struct ProductRecord {
+    identifier: String,
+    name: String,
+    price: PriceRecord,
+}
+
+#[derive(Deserialize)]
+struct TomlCatalog {
+    products: Vec<Spanned<ProductRecord>>,
+}
+
+pub struct ProductFileDataProvider {
+    path: PathBuf,
+    format: ProductFileFormat,
+    currency_parser: Box<dyn CurrencyParser>,
+}
+
+impl ProductFileDataProvider {
+    pub fn new(
+        path: impl Into<PathBuf>,
+        format: ProductFileFormat,
+        currency_parser: Box<dyn CurrencyParser>,
+    ) -> Self {
+        Self {
+            path: path.into(),
+            format,
+            currency_parser,
+        }
+    }
+
+    /// Picks the format based on the file extension.
+    pub fn from_path(
+        path: impl Into<PathBuf>,
+        currency_parser: Box<dyn CurrencyParser>,
+    ) -> Result<Self, Error> {
+        let path = path.into();
+        let format = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .and_then(ProductFileFormat::from_extension)
+            .ok_or_else(|| {
+                Error::Validation(format!(
+                    "Unsupported product file {}, expected .csv, .toml or .json",
+                    path.display()
+                ))
+            })?;
+
+        Ok(Self::new(path, format, currency_parser))
+    }
+}
+
+impl DataProvider<Product> for ProductFileDataProvider {
+    fn get_data(&self) -> Box<dyn Iterator<Item = Result<Product, Error>>> {
+        let products = match fs::read_to_string(&self.path) {
+            Ok(contents) => parse_products(&contents, self.format, self.currency_parser.as_ref()),
+            Err(error) => vec![Err(Error::Io(format!(
+                "Unable to read {}: {}",
+                self.path.display(),
+                error
+            )))],
+        };
+
+        Box::new(products.into_iter())
+    }
+}
+
+fn parse_products(
+    contents: &str,
+    format: ProductFileFormat,
+    currency_parser: &dyn CurrencyParser,
+) -> Vec<Result<Product, Error>> {
+    let records = match format {
+        ProductFileFormat::Csv => parse_csv_records(contents),
+        ProductFileFormat::Toml => parse_toml_records(contents),
+        ProductFileFormat::Json => parse_json_records(contents),
+    };
+
+    records
+        .into_iter()
+        .map(|(line, record)| {
+            record
+                .and_then(|record| to_product(record, currency_parser))
+                .map_err(|error| Error::InvalidRow {
+                    line,
+                    error: Box::new(error),
+                })
+        })
+        .collect()
+}
+
+fn parse_csv_records(contents: &str) -> Vec<(usize, Result<ProductRecord, Error>)> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(contents.as_bytes());
+
+    let headers = match reader.headers() {
+        Ok(headers) => headers.clone(),
+        Err(error) => return vec![(1, Err(Error::Parse(error.to_string())))],
+    };
+
+    reader
+        .into_records()
+        .map(|result| {
+            let position = match &result {
+                Ok(record) => record.position(),
+                Err(error) => error.position(),
+            };
+            let line = position.map_or(0, |position| position.line() as usize);
+
+            let record = result
+                .and_then(|record| record.deserialize(Some(&headers)))
+                .map_err(|error| Error::Parse(error.to_string()));
+
+            (line, record)
+        })
+        .collect()
+}
+
+fn parse_toml_records(contents: &str) -> Vec<(usize, Result<ProductRecord, Error>)> {
+    match toml::from_str::<TomlCatalog>(contents) {
+        Ok(catalog) => catalog
+            .products
+            .into_iter()
+            .map(|record| {
+                let line = line_at_offset(contents, record.span().start);
+                (line, Ok(record.into_inner()))
+            })
+            .collect(),
+        Err(error) => {
+            let line = error
+                .span()
+                .map_or(1, |span| line_at_offset(contents, span.start));
+            vec![(line, Err(Error::Parse(error.message().to_string())))]
+        }
+    }
+}
+
+fn parse_json_records(contents: &str) -> Vec<(usize, Result<ProductRecord, Error>)> {
+    match serde_json::from_str::<Vec<&RawValue>>(contents) {
+        Ok(values) => values
+            .into_iter()
+            .map(|value| {
+                let offset = value.get().as_ptr() as usize - contents.as_ptr() as usize;
+                let record = serde_json::from_str::<ProductRecord>(value.get())
+                    .map_err(|error| Error::Parse(error.to_string()));
+
+                (line_at_offset(contents, offset), record)
+            })
+            .collect(),
+        Err(error) => vec![(error.line(), Err(Error::Parse(error.to_string())))],
+    }
+}
+
+fn to_product(
+    record: ProductRecord,
+    currency_parser: &dyn CurrencyParser,
+) -> Result<Product, Error> {
+    let identifier = record.identifier.trim().to_lowercase();
+    if identifier.is_empty() || identifier.contains(char::is_whitespace) {
+        return Err(Error::Validation(format!(
+            "Invalid product identifier \"{}\"",
+            record.identifier
+        )));
+    }
+
+    let price = match record.price {
+        PriceRecord::Text(text) => currency_parser.parse_text(text.trim())?,
+        PriceRecord::Number(number) => currency_parser.parse_text(&number.to_string())?,
+    };
+
+    if price < 0 {
+        return Err(Error::Validation(format!(
+            "Price of \"{}\" must not be negative",
+            identifier
+        )));
+    }
+
+    Ok(Product {
+        identifier,
+        name: record.name.trim().to_string(),
+        price,
+        available: true,
+        stock: None,
+    })
+}
+
+fn line_at_offset(contents: &str, offset: usize) -> usize {
+    contents[..offset].matches('\n').count() + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::currency_handling::currency_parser::CurrencyParserImpl;
+
+    use super::*;
+
+    fn product(identifier: &str, name: &str, price: i32) -> Product {
+        Product {
+            identifier: identifier.to_string(),
+            name: name.to_string(),
+            price,
+            available: true,
+            stock: None,
+        }
+    }
+
+    fn assert_products(expected: Vec<Result<Product, Error>>, actual: Vec<Result<Product, Error>>) {
+        assert_eq!(expected.len(), actual.len());
+
+        for (expected, actual) in expected.into_iter().zip(actual) {
+            match (expected, actual) {
+                (Ok(expected), Ok(actual)) => {
+                    assert_eq!(expected.identifier, actual.identifier);
+                    assert_eq!(expected.name, actual.name);
+                    assert_eq!(expected.price, actual.price);
+                }
+                (expected, actual) => assert_eq!(expected, actual),
+            }
+        }
+    }
+
+    #[test]
+    fn parses_csv() {
+        let contents = "identifier,name,price\n\
+                        coke, a coke, 4.20\n\
+                        Mate,Club-Mate,2.-\n\
+                        beer,beer,cheap\n";
+
+        assert_products(
+            vec![
+                Ok(product("coke", "a coke", 420)),
+                Ok(product("mate", "Club-Mate", 200)),
+                Err(Error::InvalidRow {
+                    line: 4,
+                    error: Box::new(Error::Parse("cheap".to_string())),
+                }),
+            ],
+            parse_products(contents, ProductFileFormat::Csv, &CurrencyParserImpl),
+        );
+    }
+
+    #[test]
+    fn parses_toml() {
+        let contents = r#"
+[[products]]
+identifier = "coke"
+name = "a coke"
+price = "4.20"
+
+[[products]]
+identifier = "coffee"
+name = "Coffee"
+price = 1.5
+
+[[products]]
+identifier = "free"
+name = "Tap water"
+price = "-1.-"
+"#;
+
+        assert_products(
+            vec![
+                Ok(product("coke", "a coke", 420)),
+                Ok(product("coffee", "Coffee", 150)),
+                Err(Error::InvalidRow {
+                    line: 12,
+                    error: Box::new(Error::Validation(
+                        "Price of \"free\" must not be negative".to_string(),
+                    )),
+                }),
+            ],
+            parse_products(contents, ProductFileFormat::Toml, &CurrencyParserImpl),
+        );
+    }
+
+    #[test]
+    fn parses_json() {
+        let contents = r#"[
+    { "identifier": "coke", "name": "a coke", "price": "4.20" },
+    { "identifier": "tea", "name": "Tea" },
+    { "identifier": "mate", "name": "Club-Mate", "price": 2 }
+]"#;
+
+        let products = parse_products(contents, ProductFileFormat::Json, &CurrencyParserImpl);
+
+        assert_eq!(3, products.len());
+        assert_eq!(
+            &product("coke", "a coke", 420),
+            products[0].as_ref().unwrap()
+        );
+        match &products[1] {
+            Err(Error::InvalidRow { line, .. }) => assert_eq!(3, *line),
+            other => panic!("Unexpected result {:?}", other),
+        }
+        assert_eq!(200, products[2].as_ref().unwrap().price);
+    }
+
+    #[test]
+    fn detects_format_from_extension() {
+        let provider =
+            ProductFileDataProvider::from_path("catalog.TOML", Box::new(CurrencyParserImpl))
+                .unwrap();
+        assert_eq!(ProductFileFormat::Toml, provider.format);
+
+        ProductFileDataProvider::from_path("catalog.xlsx", Box::new(CurrencyParserImpl))
+            .err()
+            .unwrap();
+    }
+
+    #[test]
+    fn reports_missing_file() {
+        let provider = ProductFileDataProvider::new(
+            "/nonexistent/catalog.csv",
+            ProductFileFormat::Csv,
+            Box::new(CurrencyParserImpl),
+        );
+
+        let results = provider.get_data().collect::<Vec<_>>();
+        assert_eq!(1, results.len());
+        assert!(matches!(results[0], Err(Error::Io(_))));
+    }
+}
@@ -1,14 +1,48 @@
-use diesel::{RunQueryDsl, SqliteConnection};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use chrono::Utc;
+use diesel::{Connection, ExpressionMethods, QueryDsl, RunQueryDsl, SqliteConnection};
 
 use data_provider::*;
 
+use crate::error::Error;
 use crate::models::Product;
 use crate::schema::products;
+use crate::services::product_service::record_price;
 
 pub mod data_provider;
+pub mod file_data_provider;
+
+/// The changes made to the product catalog by a single load.
+/// Removed products are marked as unavailable instead of being deleted.
+#[derive(Default, Debug, PartialEq)]
+pub struct SyncReport {
+    pub added: Vec<Product>,
+    pub updated: Vec<Product>,
+    pub removed: Vec<Product>,
+}
+
+impl SyncReport {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.updated.is_empty() && self.removed.is_empty()
+    }
+}
+
+impl fmt::Display for SyncReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} added, {} updated, {} removed",
+            self.added.len(),
+            self.updated.len(),
+            self.removed.len()
+        )
+    }
+}
 
 pub trait DataLoader {
-    fn load_product_data(&self) -> Result<(), ()>;
+    fn load_product_data(&self) -> Result<SyncReport, Error>;
 }
 
 pub struct DataLoaderImpl<'a> {
@@ -29,25 +63,81 @@ impl<'a> DataLoaderImpl<'a> {
 }
 
 impl DataLoader for DataLoaderImpl<'_> {
-    fn load_product_data(&self) -> Result<(), ()> {
-        diesel::delete(products::table)
-            .execute(self.database_connection)
-            .map_err(|_| ())?;
-
-        self.product_data_provider
-            .get_data()
-            .map(|result| {
-                result.map(|product| {
-                    diesel::insert_into(products::table)
-                        .values(product)
-                        .execute(self.database_connection)
-                })
-            })
-            .find(|result| match result {
-                Ok(Ok(_)) => false,
-                _ => true,
-            })
-            .map_or_else(|| Ok(()), |_| Err(()))
+    fn load_product_data(&self) -> Result<SyncReport, Error> {
+        self.database_connection.transaction(|| {
+            let mut existing_products = products::table
+                .load::<Product>(self.database_connection)?
+                .into_iter()
+                .map(|product| (product.identifier.clone(), product))
+                .collect::<HashMap<_, _>>();
+
+            let timestamp = Utc::now().naive_utc();
+            let mut report = SyncReport::default();
+            let mut seen_identifiers = HashSet::new();
+
+            for product in self.product_data_provider.get_data() {
+                let product = product?;
+
+                if !seen_identifiers.insert(product.identifier.clone()) {
+                    return Err(Error::Validation(format!(
+                        "Duplicate product identifier \"{}\"",
+                        product.identifier
+                    )));
+                }
+
+                match existing_products.remove(&product.identifier) {
+                    None => {
+                        diesel::insert_into(products::table)
+                            .values(&product)
+                            .execute(self.database_connection)?;
+                        record_price(
+                            self.database_connection,
+                            &product.identifier,
+                            product.price,
+                            timestamp,
+                        )?;
+                        report.added.push(product);
+                    }
+                    Some(existing_product)
+                        if existing_product.name != product.name
+                            || existing_product.price != product.price
+                            || existing_product.available != product.available =>
+                    {
+                        diesel::update(&product)
+                            .set((
+                                products::name.eq(&product.name),
+                                products::price.eq(product.price),
+                                products::available.eq(product.available),
+                            ))
+                            .execute(self.database_connection)?;
+                        record_price(
+                            self.database_connection,
+                            &product.identifier,
+                            product.price,
+                            timestamp,
+                        )?;
+                        report.updated.push(product);
+                    }
+                    Some(_) => (),
+                }
+            }
+
+            let mut removed_products = existing_products
+                .into_values()
+                .filter(|product| product.available)
+                .collect::<Vec<_>>();
+            removed_products.sort_by(|a, b| a.identifier.cmp(&b.identifier));
+
+            for product in &mut removed_products {
+                diesel::update(&*product)
+                    .set(products::available.eq(false))
+                    .execute(self.database_connection)?;
+                product.available = false;
+            }
+            report.removed = removed_products;
+
+            Ok(report)
+        })
     }
 }
 
@@ -63,7 +153,7 @@ mod tests {
     use super::*;
 
     #[test]
-    fn empties_product_table_before_insert() {
+    fn archives_products_missing_from_new_data() {
         let database_connection = setup_in_memory_database();
 
         let mut product_data_provider = DataProviderMock::<Product>::new();
@@ -77,6 +167,8 @@ mod tests {
                     identifier: "foo".to_string(),
                     name: "foo bar".to_string(),
                     price: 120,
+                    available: true,
+                    stock: None,
                 }]
                 .into_iter()
                 .map(Ok),
@@ -90,6 +182,8 @@ mod tests {
                     identifier: "bar".to_string(),
                     name: "bar baz".to_string(),
                     price: 250,
+                    available: true,
+                    stock: None,
                 }]
                 .into_iter()
                 .map(Ok),
@@ -102,13 +196,161 @@ mod tests {
         data_loader.load_product_data().unwrap();
 
         let products = products::dsl::products
+            .filter(products::available.eq(true))
             .load::<Product>(&database_connection)
             .unwrap();
         assert_eq!(1, products.len());
 
-        let product = products.get(0).unwrap();
+        let product = products.first().unwrap();
         assert_eq!("bar", product.identifier);
         assert_eq!("bar baz", product.name);
         assert_eq!(250, product.price);
+
+        let archived_product = products::dsl::products
+            .find("foo")
+            .first::<Product>(&database_connection)
+            .unwrap();
+        assert!(!archived_product.available);
+    }
+
+    fn product(identifier: &str, name: &str, price: i32) -> Product {
+        Product {
+            identifier: identifier.to_string(),
+            name: name.to_string(),
+            price,
+            available: true,
+            stock: None,
+        }
+    }
+
+    #[test]
+    fn reports_changes() {
+        let database_connection = setup_in_memory_database();
+        diesel::insert_into(products::table)
+            .values(&vec![
+                product("coke", "a coke", 420),
+                product("mate", "Club-Mate", 200),
+                product("tea", "Tea", 100),
+            ])
+            .execute(&database_connection)
+            .unwrap();
+
+        let mut product_data_provider = DataProviderMock::<Product>::new();
+        product_data_provider
+            .expect_get_data()
+            .times(1)
+            .returns_once(Box::new(
+                vec![
+                    product("coke", "a coke", 450),
+                    product("mate", "Club-Mate", 200),
+                    product("coffee", "Coffee", 150),
+                ]
+                .into_iter()
+                .map(Ok),
+            ));
+
+        let data_loader =
+            DataLoaderImpl::new(&database_connection, Box::new(product_data_provider));
+
+        let report = data_loader.load_product_data().unwrap();
+        assert_eq!(vec![product("coffee", "Coffee", 150)], report.added);
+        assert_eq!(vec![product("coke", "a coke", 450)], report.updated);
+        assert_eq!(vec![product("tea", "Tea", 100)], report.removed);
+        assert!(!report.removed[0].available);
+
+        let coke = products::dsl::products
+            .find("coke")
+            .first::<Product>(&database_connection)
+            .unwrap();
+        assert_eq!(450, coke.price);
+    }
+
+    #[test]
+    fn keeps_catalog_on_error() {
+        let database_connection = setup_in_memory_database();
+        diesel::insert_into(products::table)
+            .values(&product("coke", "a coke", 420))
+            .execute(&database_connection)
+            .unwrap();
+
+        let mut product_data_provider = DataProviderMock::<Product>::new();
+        product_data_provider
+            .expect_get_data()
+            .times(1)
+            .returns_once(Box::new(
+                vec![
+                    Ok(product("mate", "Club-Mate", 200)),
+                    Err(Error::Parse("cheap".to_string())),
+                ]
+                .into_iter(),
+            ));
+
+        let data_loader =
+            DataLoaderImpl::new(&database_connection, Box::new(product_data_provider));
+
+        data_loader.load_product_data().unwrap_err();
+
+        let products = products::dsl::products
+            .load::<Product>(&database_connection)
+            .unwrap();
+        assert_eq!(vec![product("coke", "a coke", 420)], products);
+    }
+
+    #[test]
+    fn rejects_duplicate_identifiers() {
+        let database_connection = setup_in_memory_database();
+
+        let mut product_data_provider = DataProviderMock::<Product>::new();
+        product_data_provider
+            .expect_get_data()
+            .times(1)
+            .returns_once(Box::new(
+                vec![product("coke", "a coke", 420), product("coke", "Cola", 400)]
+                    .into_iter()
+                    .map(Ok),
+            ));
+
+        let data_loader =
+            DataLoaderImpl::new(&database_connection, Box::new(product_data_provider));
+
+        assert_eq!(
+            Err(Error::Validation(
+                "Duplicate product identifier \"coke\"".to_string()
+            )),
+            data_loader.load_product_data()
+        );
+    }
+
+    #[test]
+    fn restores_archived_products() {
+        let database_connection = setup_in_memory_database();
+        diesel::insert_into(products::table)
+            .values(&Product {
+                available: false,
+                stock: None,
+                ..product("coke", "a coke", 420)
+            })
+            .execute(&database_connection)
+            .unwrap();
+
+        let mut product_data_provider = DataProviderMock::<Product>::new();
+        product_data_provider
+            .expect_get_data()
+            .times(1)
+            .returns_once(Box::new(
+                vec![product("coke", "a coke", 420)].into_iter().map(Ok),
+            ));
+
+        let data_loader =
+            DataLoaderImpl::new(&database_connection, Box::new(product_data_provider));
+
+        let report = data_loader.load_product_data().unwrap();
+        assert_eq!(vec![product("coke", "a coke", 420)], report.updated);
+
+        let coke = products::dsl::products
+            .find("coke")
+            .first::<Product>(&database_connection)
+            .unwrap();
+        assert!(coke.available);
     }
 }
@@ -0,0 +1,84 @@
+use std::fmt;
+
+use diesel::migration::RunMigrationsError;
+
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    Database(diesel::result::Error),
+    Migration(RunMigrationsError),
+    Network(String),
+    Io(String),
+    Parse(String),
+    Validation(String),
+    NotFound(String),
+    InvalidRow { line: usize, error: Box<Error> },
+}
+
+impl Error {
+    /// Whether the error was caused by the user's input, as opposed to a problem
+    /// with the bot itself. Only user errors are shown verbatim in responses.
+    pub fn is_user_error(&self) -> bool {
+        match self {
+            Error::Database(_) | Error::Migration(_) | Error::Network(_) | Error::Io(_) => false,
+            Error::Parse(_) | Error::Validation(_) | Error::NotFound(_) => true,
+            Error::InvalidRow { error, .. } => error.is_user_error(),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Database(error) => write!(f, "Database error: {}", error),
+            Error::Migration(error) => write!(f, "Migration error: {}", error),
+            Error::Network(reason) => write!(f, "Network error: {}", reason),
+            Error::Io(reason) => write!(f, "I/O error: {}", reason),
+            Error::Parse(text) => write!(f, "Could not parse \"{}\"", text),
+            Error::Validation(reason) => write!(f, "{}", reason),
+            Error::NotFound(subject) => write!(f, "{} not found", subject),
+            Error::InvalidRow { line, error } => write!(f, "Line {}: {}", line, error),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Database(error) => Some(error),
+            Error::Migration(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<diesel::result::Error> for Error {
+    fn from(error: diesel::result::Error) -> Self {
+        Error::Database(error)
+    }
+}
+
+impl From<RunMigrationsError> for Error {
+    fn from(error: RunMigrationsError) -> Self {
+        Error::Migration(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn database_errors_are_not_user_errors() {
+        let error = Error::from(diesel::result::Error::NotFound);
+
+        assert!(!error.is_user_error());
+    }
+
+    #[test]
+    fn displays_not_found_subject() {
+        let error = Error::NotFound("Product \"foo\"".to_string());
+
+        assert!(error.is_user_error());
+        assert_eq!("Product \"foo\" not found", error.to_string());
+    }
+}
@@ -0,0 +1,348 @@
+use std::io::Write;
+
+use chrono::NaiveDateTime;
+use diesel::SqliteConnection;
+use diesel::{ExpressionMethods, NullableExpressionMethods, QueryDsl, RunQueryDsl};
+use serde::Serialize;
+
+use crate::currency_handling::currency_formatter::CurrencyFormatter;
+use crate::error::Error;
+use crate::models::{Rappen, Transaction};
+use crate::schema::{products, transactions, users};
+
+/// The columns of a CSV export, in the order of the fields of [`LedgerEntry`].
+const CSV_HEADER: [&str; 12] = [
+    "id",
+    "timestamp",
+    "user_id",
+    "user_name",
+    "kind",
+    "amount",
+    "amount_chf",
+    "product_identifier",
+    "product_name",
+    "quantity",
+    "unit_price",
+    "counterparty",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExportFormat {
+    Csv,
+    /// A top level array of objects.
+    Json,
+}
+
+impl ExportFormat {
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_lowercase().as_ref() {
+            "csv" => Some(ExportFormat::Csv),
+            "json" => Some(ExportFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+/// Restricts the exported transactions. Fields that are not set don't filter anything.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ExportFilter {
+    /// Inclusive.
+    pub from: Option<NaiveDateTime>,
+    /// Exclusive.
+    pub until: Option<NaiveDateTime>,
+    pub user_id: Option<String>,
+}
+
+/// A transaction together with the names of its user and product.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct LedgerEntry {
+    pub id: i32,
+    /// ISO-8601 in UTC.
+    pub timestamp: String,
+    pub user_id: String,
+    pub user_name: String,
+    /// E.g. `purchase` or `expense_reimbursement`.
+    pub kind: String,
+    pub amount: Rappen,
+    /// The amount as shown in the bot, e.g. `- 4.20`.
+    pub amount_chf: String,
+    pub product_identifier: Option<String>,
+    pub product_name: Option<String>,
+    pub quantity: Option<i32>,
+    pub unit_price: Option<Rappen>,
+    pub counterparty: Option<String>,
+}
+
+impl LedgerEntry {
+    fn new(
+        transaction: Transaction,
+        user_name: String,
+        current_product_name: Option<String>,
+        currency_formatter: &dyn CurrencyFormatter,
+    ) -> Self {
+        Self {
+            id: transaction.id,
+            timestamp: transaction
+                .timestamp
+                .format("%Y-%m-%dT%H:%M:%SZ")
+                .to_string(),
+            user_id: transaction.user,
+            user_name,
+            kind: transaction.kind.as_str().to_string(),
+            amount: transaction.amount,
+            amount_chf: currency_formatter.format_amount(transaction.amount),
+            product_identifier: transaction.product_identifier,
+            // The name at the time of the purchase takes precedence over the current one
+            product_name: transaction.product_name.or(current_product_name),
+            quantity: transaction.quantity,
+            unit_price: transaction.product_price,
+            counterparty: transaction.counterparty,
+        }
+    }
+}
+
+/// Loads the matching transactions, oldest first.
+pub fn load_ledger(
+    database_connection: &SqliteConnection,
+    filter: &ExportFilter,
+    currency_formatter: &dyn CurrencyFormatter,
+) -> Result<Vec<LedgerEntry>, Error> {
+    let mut query = transactions::table
+        .inner_join(users::table)
+        .left_join(products::table)
+        .select((
+            transactions::all_columns,
+            users::name,
+            products::name.nullable(),
+        ))
+        .order((transactions::timestamp, transactions::id))
+        .into_boxed();
+
+    if let Some(from) = filter.from {
+        query = query.filter(transactions::timestamp.ge(from));
+    }
+    if let Some(until) = filter.until {
+        query = query.filter(transactions::timestamp.lt(until));
+    }
+    if let Some(user_id) = &filter.user_id {
+        query = query.filter(transactions::user.eq(user_id));
+    }
+
+    query
+        .load::<(Transaction, String, Option<String>)>(database_connection)
+        .map(|rows| {
+            rows.into_iter()
+                .map(|(transaction, user_name, product_name)| {
+                    LedgerEntry::new(transaction, user_name, product_name, currency_formatter)
+                })
+                .collect()
+        })
+        .map_err(Error::Database)
+}
+
+pub fn write_ledger(
+    entries: &[LedgerEntry],
+    format: ExportFormat,
+    writer: impl Write,
+) -> Result<(), Error> {
+    match format {
+        ExportFormat::Csv => {
+            // The header is written explicitly so that an empty export still has it
+            let mut writer = csv::WriterBuilder::new()
+                .has_headers(false)
+                .from_writer(writer);
+            writer
+                .write_record(CSV_HEADER)
+                .map_err(|error| Error::Io(error.to_string()))?;
+            for entry in entries {
+                writer
+                    .serialize(entry)
+                    .map_err(|error| Error::Io(error.to_string()))?;
+            }
+            writer.flush().map_err(|error| Error::Io(error.to_string()))
+        }
+        ExportFormat::Json => serde_json::to_writer_pretty(writer, entries)
+            .map_err(|error| Error::Io(error.to_string())),
+    }
+}
+
+/// Writes the matching transactions and returns how many were exported.
+pub fn export_ledger(
+    database_connection: &SqliteConnection,
+    filter: &ExportFilter,
+    format: ExportFormat,
+    currency_formatter: &dyn CurrencyFormatter,
+    writer: impl Write,
+) -> Result<usize, Error> {
+    let entries = load_ledger(database_connection, filter, currency_formatter)?;
+    write_ledger(&entries, format, writer)?;
+
+    Ok(entries.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use crate::currency_handling::currency_formatter::CurrencyFormatterImpl;
+    use crate::models::{NewTransaction, Product, User};
+    use crate::test_utils::*;
+
+    use super::*;
+
+    fn timestamp(day: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 9, day)
+            .unwrap()
+            .and_hms_opt(8, 30, 0)
+            .unwrap()
+    }
+
+    fn setup_ledger() -> SqliteConnection {
+        let database_connection = setup_in_memory_database();
+
+        let anna = User {
+            id: "1".to_string(),
+            name: "Anna".to_string(),
+        };
+        let ben = User {
+            id: "2".to_string(),
+            name: "Ben".to_string(),
+        };
+        diesel::insert_into(users::table)
+            .values(&vec![anna.clone(), ben.clone()])
+            .execute(&database_connection)
+            .unwrap();
+
+        diesel::insert_into(products::table)
+            .values(&Product {
+                identifier: "coke".to_string(),
+                name: "a coke".to_string(),
+                price: 420,
+                available: true,
+                stock: None,
+            })
+            .execute(&database_connection)
+            .unwrap();
+
+        diesel::insert_into(transactions::table)
+            .values(&vec![
+                NewTransaction {
+                    timestamp: timestamp(1),
+                    ..NewTransaction::new(2000, &anna)
+                },
+                NewTransaction {
+                    timestamp: timestamp(2),
+                    product_name: Some("a coke".to_string()),
+                    product_identifier: Some("coke".to_string()),
+                    product_price: Some(420),
+                    quantity: Some(2),
+                    ..NewTransaction::new(-840, &anna)
+                },
+                NewTransaction {
+                    timestamp: timestamp(3),
+                    ..NewTransaction::new(-5, &ben)
+                },
+            ])
+            .execute(&database_connection)
+            .unwrap();
+
+        database_connection
+    }
+
+    #[test]
+    fn loads_filtered_ledger() {
+        let database_connection = setup_ledger();
+
+        let entries = load_ledger(
+            &database_connection,
+            &ExportFilter {
+                from: Some(timestamp(2)),
+                until: None,
+                user_id: Some("1".to_string()),
+            },
+            &CurrencyFormatterImpl,
+        )
+        .unwrap();
+
+        assert_eq!(1, entries.len());
+        assert_eq!("2026-09-02T08:30:00Z", entries[0].timestamp);
+        assert_eq!("Anna", entries[0].user_name);
+        assert_eq!(-840, entries[0].amount);
+        assert_eq!("- 8.40", entries[0].amount_chf);
+        assert_eq!("purchase", entries[0].kind);
+        assert_eq!(Some("a coke".to_string()), entries[0].product_name);
+        assert_eq!(Some(2), entries[0].quantity);
+    }
+
+    #[test]
+    fn exports_csv() {
+        let database_connection = setup_ledger();
+
+        let mut output = Vec::new();
+        let count = export_ledger(
+            &database_connection,
+            &ExportFilter {
+                until: Some(timestamp(2)),
+                ..ExportFilter::default()
+            },
+            ExportFormat::Csv,
+            &CurrencyFormatterImpl,
+            &mut output,
+        )
+        .unwrap();
+
+        assert_eq!(1, count);
+        assert_eq!(
+            "id,timestamp,user_id,user_name,kind,amount,amount_chf,product_identifier,\
+             product_name,quantity,unit_price,counterparty\n\
+             1,2026-09-01T08:30:00Z,1,Anna,deposit,2000,20.-,,,,,\n",
+            String::from_utf8(output).unwrap()
+        );
+    }
+
+    #[test]
+    fn exports_json() {
+        let database_connection = setup_ledger();
+
+        let mut output = Vec::new();
+        export_ledger(
+            &database_connection,
+            &ExportFilter::default(),
+            ExportFormat::Json,
+            &CurrencyFormatterImpl,
+            &mut output,
+        )
+        .unwrap();
+
+        let entries = serde_json::from_slice::<serde_json::Value>(&output).unwrap();
+        assert_eq!(3, entries.as_array().unwrap().len());
+        assert_eq!("Ben", entries[2]["user_name"]);
+        assert_eq!("- -.05", entries[2]["amount_chf"]);
+        assert_eq!(serde_json::Value::Null, entries[2]["product_name"]);
+    }
+
+    #[test]
+    fn exports_csv_header_without_transactions() {
+        let database_connection = setup_ledger();
+
+        let mut output = Vec::new();
+        let count = export_ledger(
+            &database_connection,
+            &ExportFilter {
+                user_id: Some("unknown".to_string()),
+                ..ExportFilter::default()
+            },
+            ExportFormat::Csv,
+            &CurrencyFormatterImpl,
+            &mut output,
+        )
+        .unwrap();
+
+        assert_eq!(0, count);
+        assert_eq!(
+            "id,timestamp,user_id,user_name,kind,amount,amount_chf,product_identifier,\
+             product_name,quantity,unit_price,counterparty\n",
+            String::from_utf8(output).unwrap()
+        );
+    }
+}
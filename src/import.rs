@@ -0,0 +1,412 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io::Read;
+
+use chrono::{NaiveDate, NaiveDateTime};
+use diesel::SqliteConnection;
+use diesel::{Connection, ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::currency_handling::currency_parser::CurrencyParser;
+use crate::error::Error;
+use crate::models::{NewTransaction, Product, Rappen, User};
+use crate::schema::{products, transactions, users};
+
+#[derive(Deserialize)]
+struct LedgerRecord {
+    user_id: String,
+    timestamp: String,
+    amount: String,
+    #[serde(default)]
+    product: Option<String>,
+}
+
+/// The outcome of an import. Rows are referenced by their line number.
+/// If there are any errors, nothing was imported.
+#[derive(Default, Debug, PartialEq)]
+pub struct ImportReport {
+    pub imported: Vec<usize>,
+    /// Rows that were already imported before.
+    pub duplicates: Vec<usize>,
+    pub errors: Vec<Error>,
+}
+
+impl ImportReport {
+    pub fn is_successful(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+impl fmt::Display for ImportReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} imported, {} duplicates, {} errors",
+            self.imported.len(),
+            self.duplicates.len(),
+            self.errors.len()
+        )
+    }
+}
+
+/// Imports transactions from CSV ledgers with a `user_id,timestamp,amount,product` header.
+/// The product column is optional.
+pub struct LedgerImporter<'a> {
+    database_connection: &'a SqliteConnection,
+    currency_parser: Box<dyn CurrencyParser + 'a>,
+}
+
+impl<'a> LedgerImporter<'a> {
+    pub fn new(
+        database_connection: &'a SqliteConnection,
+        currency_parser: Box<dyn CurrencyParser + 'a>,
+    ) -> Self {
+        Self {
+            database_connection,
+            currency_parser,
+        }
+    }
+
+    /// Imports all rows in a single database transaction.
+    pub fn import_ledger(&self, reader: impl Read) -> Result<ImportReport, Error> {
+        let mut reader = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .flexible(true)
+            .from_reader(reader);
+
+        let headers = reader
+            .headers()
+            .map_err(|error| Error::Parse(error.to_string()))?
+            .clone();
+
+        self.database_connection.transaction(|| {
+            let mut report = ImportReport::default();
+            let mut transactions = Vec::new();
+            // Ledgers with dates only can legitimately repeat a row, e.g. two coffees on the same day
+            let mut occurrences = HashMap::new();
+
+            for result in reader.into_records() {
+                let position = match &result {
+                    Ok(record) => record.position(),
+                    Err(error) => error.position(),
+                };
+                let line = position.map_or(0, |position| position.line() as usize);
+
+                let transaction = result
+                    .map_err(|error| Error::Parse(error.to_string()))
+                    .and_then(|record| {
+                        record
+                            .deserialize::<LedgerRecord>(Some(&headers))
+                            .map_err(|error| Error::Parse(error.to_string()))
+                    })
+                    .and_then(|record| self.to_transaction(record));
+
+                match transaction {
+                    Ok(transaction) => {
+                        let occurrence = occurrences
+                            .entry((
+                                transaction.user.clone(),
+                                transaction.timestamp,
+                                transaction.amount,
+                                transaction.product_identifier.clone(),
+                            ))
+                            .or_insert(0);
+                        let import_hash = hash_record(
+                            &transaction.user,
+                            transaction.timestamp,
+                            transaction.amount,
+                            transaction.product_identifier.as_deref(),
+                            *occurrence,
+                        );
+                        *occurrence += 1;
+
+                        if self.is_already_imported(&import_hash)? {
+                            report.duplicates.push(line);
+                        } else {
+                            report.imported.push(line);
+                            transactions.push(NewTransaction {
+                                import_hash: Some(import_hash),
+                                ..transaction
+                            });
+                        }
+                    }
+                    Err(error @ Error::Database(_)) => return Err(error),
+                    Err(error) => report.errors.push(Error::InvalidRow {
+                        line,
+                        error: Box::new(error),
+                    }),
+                }
+            }
+
+            if !report.is_successful() {
+                report.imported.clear();
+                return Ok(report);
+            }
+
+            diesel::insert_into(transactions::table)
+                .values(&transactions)
+                .execute(self.database_connection)
+                .map_err(Error::Database)?;
+
+            Ok(report)
+        })
+    }
+
+    fn to_transaction(&self, record: LedgerRecord) -> Result<NewTransaction, Error> {
+        let user = users::table
+            .find(&record.user_id)
+            .select((users::id, users::name))
+            .first::<User>(self.database_connection)
+            .optional()
+            .map_err(Error::Database)?
+            .ok_or_else(|| Error::NotFound(format!("User \"{}\"", record.user_id)))?;
+
+        let timestamp = parse_timestamp(&record.timestamp)?;
+
+        let amount = self.currency_parser.parse_text(&record.amount)?;
+        if amount == 0 {
+            return Err(Error::Validation("Amount must not be zero".to_string()));
+        }
+
+        let product = match record.product.filter(|product| !product.is_empty()) {
+            Some(identifier) => Some(
+                products::table
+                    .find(identifier.to_lowercase())
+                    .first::<Product>(self.database_connection)
+                    .optional()
+                    .map_err(Error::Database)?
+                    .ok_or_else(|| Error::NotFound(format!("Product \"{}\"", identifier)))?,
+            ),
+            None => None,
+        };
+
+        Ok(NewTransaction {
+            timestamp,
+            product_name: product.as_ref().map(|product| product.name.clone()),
+            product_identifier: product.as_ref().map(|product| product.identifier.clone()),
+            product_price: product.as_ref().map(|_| -amount),
+            quantity: product.as_ref().map(|_| 1),
+            ..NewTransaction::new(amount, &user)
+        })
+    }
+
+    fn is_already_imported(&self, import_hash: &str) -> Result<bool, Error> {
+        transactions::table
+            .filter(transactions::import_hash.eq(import_hash))
+            .select(transactions::id)
+            .first::<i32>(self.database_connection)
+            .optional()
+            .map(|transaction| transaction.is_some())
+            .map_err(Error::Database)
+    }
+}
+
+/// Accepts ISO-8601 timestamps and dates as well as `31.12.2019` style dates.
+fn parse_timestamp(text: &str) -> Result<NaiveDateTime, Error> {
+    let text = text.trim_end_matches('Z');
+
+    ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(text, format).ok())
+        .or_else(|| {
+            ["%Y-%m-%d", "%d.%m.%Y"]
+                .iter()
+                .find_map(|format| NaiveDate::parse_from_str(text, format).ok())
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+        })
+        .ok_or_else(|| Error::Parse(text.to_string()))
+}
+
+/// A hash over the normalized fields of a row, so the same row always gets the same hash.
+/// Repetitions of a row within a ledger are told apart by their occurrence, starting at 0.
+fn hash_record(
+    user_id: &str,
+    timestamp: NaiveDateTime,
+    amount: Rappen,
+    product_identifier: Option<&str>,
+    occurrence: usize,
+) -> String {
+    let timestamp = timestamp.format("%Y-%m-%dT%H:%M:%S").to_string();
+    let amount = amount.to_string();
+    let occurrence_field = occurrence.to_string();
+
+    let mut fields = vec![
+        user_id,
+        &timestamp,
+        &amount,
+        product_identifier.unwrap_or_default(),
+    ];
+    // The first occurrence keeps the hash of ledgers imported before repetitions were counted
+    if occurrence > 0 {
+        fields.push(&occurrence_field);
+    }
+
+    hash_fields(&fields)
+}
+
+/// Hex encoded SHA-256 hash of the fields, separated by unit separators.
+pub(crate) fn hash_fields(fields: &[&str]) -> String {
+    Sha256::digest(fields.join("\u{1f}").as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::currency_handling::currency_parser::CurrencyParserImpl;
+    use crate::models::Transaction;
+    use crate::test_utils::*;
+
+    use super::*;
+
+    fn setup_database() -> SqliteConnection {
+        let database_connection = setup_in_memory_database();
+
+        diesel::insert_into(users::table)
+            .values(&User {
+                id: "1".to_string(),
+                name: "Anna".to_string(),
+            })
+            .execute(&database_connection)
+            .unwrap();
+
+        diesel::insert_into(products::table)
+            .values(&Product {
+                identifier: "coke".to_string(),
+                name: "a coke".to_string(),
+                price: 420,
+                available: true,
+                stock: None,
+            })
+            .execute(&database_connection)
+            .unwrap();
+
+        database_connection
+    }
+
+    #[test]
+    fn imports_ledger_and_skips_duplicates() {
+        let database_connection = setup_database();
+        let importer = LedgerImporter::new(&database_connection, Box::new(CurrencyParserImpl));
+
+        let ledger = "user_id,timestamp,amount,product\n\
+                      1,2019-10-01T08:00:00Z,20.-,\n\
+                      1,01.10.2019,-4.20,coke\n";
+
+        let report = importer.import_ledger(ledger.as_bytes()).unwrap();
+        assert_eq!(vec![2, 3], report.imported);
+        assert!(report.duplicates.is_empty());
+        assert!(report.errors.is_empty());
+
+        let ledger = "user_id,timestamp,amount,product\n\
+                      1,2019-10-01 08:00:00,20.-\n\
+                      1,01.10.2019,-4.20,coke\n\
+                      1,2019-10-02,5.-\n";
+
+        let report = importer.import_ledger(ledger.as_bytes()).unwrap();
+        assert_eq!(vec![4], report.imported);
+        assert_eq!(vec![2, 3], report.duplicates);
+
+        let transactions = transactions::table
+            .order(transactions::id)
+            .load::<Transaction>(&database_connection)
+            .unwrap();
+        assert_eq!(3, transactions.len());
+        assert_eq!(2000, transactions[0].amount);
+        assert_eq!(Some("a coke".to_string()), transactions[1].product_name);
+        assert_eq!(Some(420), transactions[1].product_price);
+    }
+
+    #[test]
+    fn repeated_rows_within_a_ledger_are_imported() {
+        let database_connection = setup_database();
+        let importer = LedgerImporter::new(&database_connection, Box::new(CurrencyParserImpl));
+
+        let ledger = "user_id,timestamp,amount,product\n\
+                      1,01.10.2019,-4.20,coke\n\
+                      1,01.10.2019,-4.20,coke\n";
+
+        let report = importer.import_ledger(ledger.as_bytes()).unwrap();
+        assert_eq!(vec![2, 3], report.imported);
+        assert!(report.duplicates.is_empty());
+
+        let ledger = "user_id,timestamp,amount,product\n\
+                      1,01.10.2019,-4.20,coke\n\
+                      1,01.10.2019,-4.20,coke\n\
+                      1,01.10.2019,-4.20,coke\n";
+
+        let report = importer.import_ledger(ledger.as_bytes()).unwrap();
+        assert_eq!(vec![4], report.imported);
+        assert_eq!(vec![2, 3], report.duplicates);
+
+        let count = transactions::table
+            .count()
+            .get_result::<i64>(&database_connection)
+            .unwrap();
+        assert_eq!(3, count);
+    }
+
+    #[test]
+    fn invalid_rows_abort_import() {
+        let database_connection = setup_database();
+        let importer = LedgerImporter::new(&database_connection, Box::new(CurrencyParserImpl));
+
+        let ledger = "user_id,timestamp,amount\n\
+                      1,2019-10-01,20.-\n\
+                      2,2019-10-01,20.-\n\
+                      1,yesterday,20.-\n\
+                      1,2019-10-02,lots\n";
+
+        let report = importer.import_ledger(ledger.as_bytes()).unwrap();
+        assert!(report.imported.is_empty());
+        assert_eq!(
+            vec![
+                Error::InvalidRow {
+                    line: 3,
+                    error: Box::new(Error::NotFound("User \"2\"".to_string())),
+                },
+                Error::InvalidRow {
+                    line: 4,
+                    error: Box::new(Error::Parse("yesterday".to_string())),
+                },
+                Error::InvalidRow {
+                    line: 5,
+                    error: Box::new(Error::Parse("lots".to_string())),
+                },
+            ],
+            report.errors
+        );
+
+        let count = transactions::table
+            .count()
+            .get_result::<i64>(&database_connection)
+            .unwrap();
+        assert_eq!(0, count);
+    }
+
+    #[test]
+    fn hash_is_stable() {
+        let timestamp = parse_timestamp("2019-10-01T08:00:00Z").unwrap();
+
+        assert_eq!(
+            hash_record("1", timestamp, 2000, None, 0),
+            hash_record(
+                "1",
+                parse_timestamp("2019-10-01 08:00:00").unwrap(),
+                2000,
+                None,
+                0
+            )
+        );
+        assert_ne!(
+            hash_record("1", timestamp, 2000, None, 0),
+            hash_record("1", timestamp, 2000, Some("coke"), 0)
+        );
+        assert_ne!(
+            hash_record("1", timestamp, 2000, None, 0),
+            hash_record("1", timestamp, 2000, None, 1)
+        );
+    }
+}
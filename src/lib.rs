@@ -1,5 +1,7 @@
 #![allow(dead_code)]
 #![allow(unused_imports)]
+#![allow(non_local_definitions)]
+#![allow(clippy::default_constructed_unit_structs)]
 
 #[macro_use]
 extern crate diesel;
@@ -11,24 +13,45 @@ use diesel::SqliteConnection;
 
 use crate::models::{Product, User};
 
+pub use crate::error::Error;
+
+pub mod bank_import;
+
+pub mod config;
+
 pub mod currency_handling;
 
+mod error;
+
+pub mod export;
+
+pub mod import;
+
+mod markdown;
+
 pub mod message_handler;
 pub mod message_router;
 
 pub mod models;
 mod schema;
 
+pub mod qr_bill;
+
 pub mod data_loader;
 
 pub mod services;
 
+pub mod statement_renderer;
+
+#[cfg(feature = "telegram")]
+pub mod telegram;
+
 #[cfg(test)]
 mod test_utils;
 
 embed_migrations!("migrations");
-pub fn run_migrations(database_connection: &SqliteConnection) -> Result<(), ()> {
-    embedded_migrations::run(database_connection).map_err(|_| ())
+pub fn run_migrations(database_connection: &SqliteConnection) -> Result<(), Error> {
+    embedded_migrations::run(database_connection).map_err(Error::Migration)
 }
 
 #[cfg(test)]
@@ -38,6 +61,7 @@ mod tests {
     use currency_parser::CurrencyParser;
 
     use crate::models::Rappen;
+    use crate::test_utils::*;
 
     use super::*;
 
@@ -60,4 +84,20 @@ mod tests {
     fn parses_and_formats_negative_rappen_amount() {
         format_and_parse(-50)
     }
+
+    #[test]
+    fn migrations_can_be_reverted() {
+        let database_connection = setup_in_memory_database();
+        let migrations_directory =
+            std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("migrations");
+
+        let migration_count = std::fs::read_dir(&migrations_directory).unwrap().count();
+        for _ in 0..migration_count {
+            diesel_migrations::revert_latest_migration_in_directory(
+                &database_connection,
+                &migrations_directory,
+            )
+            .unwrap();
+        }
+    }
 }
@@ -0,0 +1,74 @@
+/// Escapes the characters that have a meaning in Telegram's Markdown,
+/// so names and descriptions are shown verbatim.
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for character in text.chars() {
+        if is_special(character) {
+            escaped.push('\\');
+        }
+        escaped.push(character);
+    }
+    escaped
+}
+
+/// Marks text as bold, escaping it like [`escape`].
+///
+/// Escapes are not allowed inside an entity, so the bold parts end before every
+/// special character and start again after it.
+pub fn bold(text: &str) -> String {
+    let mut bold = String::with_capacity(text.len() + 2);
+    let mut part = String::new();
+    for character in text.chars() {
+        if is_special(character) {
+            push_bold_part(&mut bold, &mut part);
+            bold.push('\\');
+            bold.push(character);
+        } else {
+            part.push(character);
+        }
+    }
+    push_bold_part(&mut bold, &mut part);
+    bold
+}
+
+fn push_bold_part(bold: &mut String, part: &mut String) {
+    if !part.is_empty() {
+        bold.push('*');
+        bold.push_str(part);
+        bold.push('*');
+        part.clear();
+    }
+}
+
+fn is_special(character: char) -> bool {
+    matches!(character, '_' | '*' | '`' | '[')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_markdown_characters() {
+        assert_eq!(
+            "ice\\_tea \\*new\\* \\`x\\` \\[1]",
+            escape("ice_tea *new* `x` [1]")
+        );
+    }
+
+    #[test]
+    fn bold_text_without_markdown_characters() {
+        assert_eq!("*- Anna (10.-)*", bold("- Anna (10.-)"));
+    }
+
+    #[test]
+    fn escapes_outside_of_bold_parts() {
+        assert_eq!("*- Anna*\\_*B (1.-)*", bold("- Anna_B (1.-)"));
+        assert_eq!("\\**Anna*\\*", bold("*Anna*"));
+    }
+
+    #[test]
+    fn leaves_plain_text_untouched() {
+        assert_eq!("Anna Muster (- 1.50)", escape("Anna Muster (- 1.50)"));
+    }
+}
@@ -1,23 +1,38 @@
 use chrono::prelude::Utc;
+use chrono::Duration;
+use chrono::{Datelike, NaiveDate};
 use diesel::{insert_into, update, ExpressionMethods, QueryDsl, RunQueryDsl, SqliteConnection};
 use diesel_migrations::name;
+#[cfg(test)]
+use mockiato::mockable;
 
 use balances::dsl::balances as balances_dsl;
 use products::dsl::products as products_dsl;
 use transactions::dsl::transactions as transactions_dsl;
 use users::dsl::users as users_dsl;
 
-use crate::currency_handling::currency_formatter::CurrencyFormatter;
-use crate::message_router::MessageRouter;
+use crate::config::{Config, OutOfStockPolicy};
+use crate::currency_handling::currency_formatter::{CurrencyFormatter, CurrencyFormatterImpl};
+use crate::currency_handling::currency_parser::CurrencyParserImpl;
+use crate::error::Error;
+use crate::markdown::{bold, escape};
+use crate::message_router::{MessageRouter, MessageRouterImpl};
 use crate::models::{
-    Balance, Command, Message, MessageAction, Product, Rappen, Response, Transaction, User,
+    Attachment, Balance, Command, Message, MessageAction, Product, ProductPrice, Rappen, Response,
+    Role, Transaction, TransactionKind, User,
 };
+use crate::qr_bill::{format_iban, QrBill, Reference};
 use crate::schema::{balances, products, transactions, users};
-use crate::services::balance_service::BalanceService;
-use crate::services::product_service::ProductService;
-use crate::services::transaction_service::TransactionService;
-use crate::services::user_service::UserService;
+use crate::services::balance_service::{BalanceService, BalanceServiceImpl};
+use crate::services::product_service::{ProductService, ProductServiceImpl};
+use crate::services::statement_service::{StatementService, StatementServiceImpl};
+use crate::services::transaction_service::{TransactionService, TransactionServiceImpl};
+use crate::services::user_service::{UserService, UserServiceImpl};
+use crate::statement_renderer::render_markdown;
+
+const UNDO_TIME_WINDOW_MINUTES: i64 = 5;
 
+#[cfg_attr(test, mockable)]
 pub trait MessageHandler {
     fn handle_message(&self, message: &Message) -> Vec<Response>;
 }
@@ -28,17 +43,22 @@ pub struct MessageHandlerImpl<'a> {
     product_service: Box<dyn ProductService + 'a>,
     transaction_service: Box<dyn TransactionService + 'a>,
     balance_service: Box<dyn BalanceService + 'a>,
+    statement_service: Box<dyn StatementService + 'a>,
     currency_formatter: Box<dyn CurrencyFormatter + 'a>,
+    config: Config,
 }
 
 impl<'a> MessageHandlerImpl<'a> {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         message_router: Box<dyn MessageRouter + 'a>,
         user_service: Box<dyn UserService + 'a>,
         product_service: Box<dyn ProductService + 'a>,
         transaction_service: Box<dyn TransactionService + 'a>,
         balance_service: Box<dyn BalanceService + 'a>,
+        statement_service: Box<dyn StatementService + 'a>,
         currency_formatter: Box<dyn CurrencyFormatter + 'a>,
+        config: Config,
     ) -> Self {
         Self {
             message_router,
@@ -46,24 +66,58 @@ impl<'a> MessageHandlerImpl<'a> {
             product_service,
             transaction_service,
             balance_service,
+            statement_service,
             currency_formatter,
+            config,
         }
     }
 
+    /// Creates a handler backed by the services operating on the given database.
+    pub fn with_database_connection(
+        database_connection: &'a SqliteConnection,
+        config: Config,
+    ) -> Self {
+        Self::new(
+            Box::new(MessageRouterImpl::new(
+                Box::new(ProductServiceImpl::new(database_connection)),
+                Box::new(CurrencyParserImpl),
+            )),
+            Box::new(UserServiceImpl::new(database_connection)),
+            Box::new(ProductServiceImpl::new(database_connection)),
+            Box::new(TransactionServiceImpl::new(database_connection)),
+            Box::new(BalanceServiceImpl::new(database_connection)),
+            Box::new(StatementServiceImpl::new(database_connection)),
+            Box::new(CurrencyFormatterImpl),
+            config,
+        )
+    }
+
     fn handle_message_action(
         &self,
         message_action: MessageAction,
         sender: &User,
-    ) -> Result<Vec<Response>, ()> {
+    ) -> Result<Vec<Response>, Error> {
         self.user_service.update_user(sender)?;
 
         let response = match &message_action {
-            MessageAction::Command(command) => self.handle_command(command, sender),
-            MessageAction::Product(product) => self.handle_product(product, sender),
-            MessageAction::Amount(amount) => self.handle_amount(*amount, sender),
-        }?;
+            MessageAction::Command(command) => self.handle_command(command, sender)?,
+            MessageAction::Product { product, quantity } => {
+                self.handle_product(product, *quantity, sender)?
+            }
+            MessageAction::Amount { amount, kind } => self.handle_amount(*amount, *kind, sender)?,
+            MessageAction::Transfer { recipient, amount } => {
+                return self.handle_transfer(recipient, *amount, sender);
+            }
+        };
 
-        if matches!(message_action, MessageAction::Product(_) | MessageAction::Amount(_)) {
+        if matches!(
+            message_action,
+            MessageAction::Product { .. }
+                | MessageAction::Amount { .. }
+                | MessageAction::Command(Command::Undo)
+                | MessageAction::Command(Command::CorrectBalance { .. })
+                | MessageAction::Command(Command::RecordExpense { .. })
+        ) {
             let balances = self.balance_service.get_balances()?;
             let formatted_balances = self.format_balances(&balances, sender);
 
@@ -71,6 +125,7 @@ impl<'a> MessageHandlerImpl<'a> {
                 response,
                 Response {
                     contents: formatted_balances,
+                    attachment: None,
                 },
             ])
         } else {
@@ -78,53 +133,519 @@ impl<'a> MessageHandlerImpl<'a> {
         }
     }
 
-    fn handle_command(&self, command: &Command, sender: &User) -> Result<Response, ()> {
+    fn is_admin(&self, user: &User) -> Result<bool, Error> {
+        if self.config.admins.contains(&user.id) {
+            return Ok(true);
+        }
+
+        Ok(self.user_service.get_role(user)? == Role::Admin)
+    }
+
+    fn handle_command(&self, command: &Command, sender: &User) -> Result<Response, Error> {
+        if command.requires_admin() && !self.is_admin(sender)? {
+            return Err(Error::Validation(
+                "Only admins are allowed to use this command".to_string(),
+            ));
+        }
+
         let contents = match command {
             Command::ListAvailableItems => {
                 let products = self.product_service.get_available_products()?;
                 self.format_products(&products)
             }
-            Command::GetCurrentBalances => {
-                let balances = self.balance_service.get_balances()?;
+            Command::GetCurrentBalances(query) => {
+                let balances = self.balance_service.query_balances(query)?;
                 self.format_balances(&balances, sender)
             }
+            Command::GetTransactionHistory(count) => {
+                let transactions = self
+                    .transaction_service
+                    .get_last_transactions(sender, *count)?;
+                self.format_transactions(&transactions)
+            }
+            Command::Undo => self.undo_last_transaction(sender)?,
+            Command::TopUp(amount) => return self.handle_top_up(*amount, sender),
+            Command::RecordExpense {
+                amount,
+                description,
+            } => {
+                if *amount <= 0 {
+                    return Err(Error::Validation("Expenses must be positive".to_string()));
+                }
+                self.transaction_service
+                    .register_expense(*amount, description, sender)?;
+                format!(
+                    "Recorded expense for {} ({})",
+                    escape(description),
+                    self.currency_formatter.format_amount(*amount)
+                )
+            }
+            Command::GetCashPosition => {
+                let cash_position = self.balance_service.get_cash_position()?;
+                format!(
+                    "Cash: {}\nDeposits: {}\nExpenses: {}\nUser balances: {}\nSurplus: {}",
+                    self.currency_formatter.format_amount(cash_position.cash()),
+                    self.currency_formatter
+                        .format_amount(cash_position.deposits),
+                    self.currency_formatter
+                        .format_amount(cash_position.expenses),
+                    self.currency_formatter
+                        .format_amount(cash_position.balances),
+                    self.currency_formatter
+                        .format_amount(cash_position.surplus())
+                )
+            }
+            Command::GetPriceHistory(product_identifier) => {
+                let product = self
+                    .product_service
+                    .get_product_with_identifier(product_identifier)?
+                    .ok_or_else(|| {
+                        Error::NotFound(format!("Product \"{}\"", product_identifier))
+                    })?;
+                let prices = self
+                    .product_service
+                    .get_price_history(&product.identifier)?;
+                self.format_price_history(&product, &prices)
+            }
+            Command::GetStatement(month) => {
+                let month = month.unwrap_or_else(|| {
+                    let today = Utc::now().naive_utc().date();
+                    NaiveDate::from_ymd_opt(today.year(), today.month(), 1).unwrap_or(today)
+                });
+                let statement = self.statement_service.get_statement(sender, month)?;
+                render_markdown(&statement, self.currency_formatter.as_ref())
+            }
+            Command::Restock {
+                product_identifier,
+                quantity,
+            } => {
+                let product = self
+                    .product_service
+                    .restock_product(product_identifier, *quantity)?;
+                format!(
+                    "Restocked {}, {} in stock",
+                    escape(&product.name),
+                    product.stock.unwrap_or(0)
+                )
+            }
+            Command::CorrectBalance { user, amount } => {
+                let user = self.user_service.get_user_with_name(user)?;
+                self.transaction_service.register_amount_transaction(
+                    *amount,
+                    TransactionKind::Correction,
+                    &user,
+                )?;
+                format!(
+                    "Corrected the balance of {} by {}",
+                    escape(&user.name),
+                    self.currency_formatter.format_amount(*amount)
+                )
+            }
+            Command::SetRole { user, role } => {
+                let user = self.user_service.get_user_with_name(user)?;
+                self.user_service.set_role(&user, *role)?;
+                match role {
+                    Role::Admin => format!("{} is now an admin", escape(&user.name)),
+                    Role::Member => format!("{} is no longer an admin", escape(&user.name)),
+                }
+            }
+            Command::SetCreditLimit { user, limit } => {
+                if limit.is_some_and(|limit| limit < 0) {
+                    return Err(Error::Validation(
+                        "Credit limits must not be negative".to_string(),
+                    ));
+                }
+                let user = self.user_service.get_user_with_name(user)?;
+                self.user_service.set_credit_limit(&user, *limit)?;
+                match limit {
+                    Some(limit) => format!(
+                        "The credit limit of {} is now {}",
+                        escape(&user.name),
+                        self.currency_formatter.format_amount(*limit)
+                    ),
+                    None => format!(
+                        "{} no longer has a personal credit limit",
+                        escape(&user.name)
+                    ),
+                }
+            }
+            Command::AddProduct {
+                identifier,
+                name,
+                price,
+            } => {
+                if identifier.is_empty() || identifier.contains(char::is_whitespace) {
+                    return Err(Error::Validation(format!(
+                        "Invalid product identifier \"{}\"",
+                        identifier
+                    )));
+                }
+                validate_price(*price)?;
+                let product = Product {
+                    identifier: identifier.clone(),
+                    name: name.clone(),
+                    price: *price,
+                    available: true,
+                    stock: None,
+                };
+                self.product_service.add_product(&product)?;
+                format!(
+                    "Added /{} - {} ({})",
+                    escape(&product.identifier),
+                    escape(&product.name),
+                    self.currency_formatter.format_amount(product.price)
+                )
+            }
+            Command::SetPrice {
+                product_identifier,
+                price,
+            } => {
+                validate_price(*price)?;
+                let product = self.product_service.set_price(product_identifier, *price)?;
+                format!(
+                    "{} now costs {}",
+                    escape(&product.name),
+                    self.currency_formatter.format_amount(product.price)
+                )
+            }
+            Command::RenameProduct {
+                product_identifier,
+                name,
+            } => {
+                let product = self
+                    .product_service
+                    .rename_product(product_identifier, name)?;
+                format!(
+                    "Renamed /{} to {}",
+                    escape(&product.identifier),
+                    escape(&product.name)
+                )
+            }
         };
 
-        Ok(Response { contents })
+        Ok(Response {
+            contents,
+            attachment: None,
+        })
     }
 
-    fn handle_product(&self, product: &Product, sender: &User) -> Result<Response, ()> {
-        self.transaction_service
-            .register_product_transaction(product, sender)?;
+    /// Creates a QR-bill to pay the given amount or the debt of the sender.
+    fn handle_top_up(&self, amount: Option<Rappen>, sender: &User) -> Result<Response, Error> {
+        let creditor = self
+            .config
+            .creditor
+            .clone()
+            .ok_or_else(|| Error::Validation("Top-ups are not set up".to_string()))?;
+
+        let amount = match amount {
+            Some(amount) if amount <= 0 => {
+                return Err(Error::Validation("The amount must be positive".to_string()))
+            }
+            Some(amount) => amount,
+            None => {
+                let balance = self.balance_service.get_balance(sender)?;
+                if balance >= 0 {
+                    return Err(Error::Validation(format!(
+                        "Your balance is {}, use /topup <amount> to pay in advance",
+                        self.currency_formatter.format_amount(balance)
+                    )));
+                }
+                -balance
+            }
+        };
+
+        let qr_bill = QrBill {
+            reference: Reference::for_user(&creditor.iban, &sender.id)?,
+            creditor,
+            amount: Some(amount),
+            message: Some(format!("Kafi top-up for {}", sender.name)),
+        };
 
         Ok(Response {
             contents: format!(
-                "Recorded {} ({})",
-                product.name,
-                self.currency_formatter.format_amount(product.price)
+                "Please pay {} to {} ({})\nReference: {}",
+                self.currency_formatter.format_amount(amount),
+                format_iban(&qr_bill.creditor.iban),
+                escape(&qr_bill.creditor.name),
+                qr_bill.reference.as_str()
+            ),
+            attachment: Some(Attachment {
+                file_name: "topup.png".to_string(),
+                data: qr_bill.render_png()?,
+            }),
+        })
+    }
+
+    fn undo_last_transaction(&self, sender: &User) -> Result<String, Error> {
+        let transaction = self
+            .transaction_service
+            .get_last_transaction(sender)?
+            .ok_or_else(|| Error::Validation("There is no transaction to undo".to_string()))?;
+
+        if Utc::now().naive_utc() - transaction.timestamp
+            > Duration::minutes(UNDO_TIME_WINDOW_MINUTES)
+        {
+            return Err(Error::Validation(format!(
+                "Only transactions from the last {} minutes can be undone",
+                UNDO_TIME_WINDOW_MINUTES
+            )));
+        }
+
+        if transaction.counterparty.is_some() {
+            return Err(Error::Validation(
+                "Transfers can't be undone, send the amount back instead".to_string(),
+            ));
+        }
+
+        if transaction.kind == TransactionKind::Correction {
+            return Err(Error::Validation(
+                "Balance corrections can only be changed by an admin".to_string(),
+            ));
+        }
+
+        self.transaction_service.delete_transaction(&transaction)?;
+
+        Ok(match &transaction.product_name {
+            Some(product_name) => format!(
+                "Reverted {} ({})",
+                escape(product_name),
+                self.currency_formatter.format_amount(-transaction.amount)
+            ),
+            None => format!(
+                "Reverted {}",
+                self.currency_formatter.format_amount(transaction.amount)
             ),
         })
     }
 
-    fn handle_amount(&self, amount: Rappen, sender: &User) -> Result<Response, ()> {
+    fn handle_product(
+        &self,
+        product: &Product,
+        quantity: u32,
+        sender: &User,
+    ) -> Result<Response, Error> {
+        let stock_notice = self.get_stock_notice(product, quantity)?;
+
+        // The same price the transaction service charges
+        let price = self
+            .product_service
+            .get_price_at(&product.identifier, Utc::now().naive_utc())?
+            .unwrap_or(product.price);
+        self.check_credit_limit(price * quantity as Rappen, sender)?;
+
+        self.transaction_service
+            .register_product_transaction(product, quantity, sender)?;
+
+        let formatted_price = self
+            .currency_formatter
+            .format_amount(price * quantity as Rappen);
+
+        let contents = if quantity == 1 {
+            format!("Recorded {} ({})", escape(&product.name), formatted_price)
+        } else {
+            format!(
+                "Recorded {}× {} ({})",
+                quantity,
+                escape(&product.name),
+                formatted_price
+            )
+        };
+
+        let contents = match stock_notice {
+            Some(stock_notice) => format!("{}\n{}", contents, stock_notice),
+            None => contents,
+        };
+
+        Ok(Response {
+            contents,
+            attachment: None,
+        })
+    }
+
+    /// Checks the stock of a product before booking it. Insufficient stock is
+    /// rejected or reported, depending on the configured policy.
+    fn get_stock_notice(&self, product: &Product, quantity: u32) -> Result<Option<String>, Error> {
+        let stock = match product.stock {
+            Some(stock) => stock,
+            None => return Ok(None),
+        };
+        let remaining_stock = stock - quantity as i32;
+
+        if remaining_stock < 0 {
+            let message = if stock > 0 {
+                format!("Only {}× {} left in stock", stock, product.name)
+            } else {
+                format!("{} is out of stock", product.name)
+            };
+
+            return match self.config.out_of_stock_policy {
+                OutOfStockPolicy::Refuse => Err(Error::Validation(message)),
+                OutOfStockPolicy::Warn => Ok(Some(format!("Warning: {}", escape(&message)))),
+            };
+        }
+
+        if remaining_stock <= self.config.low_stock_threshold {
+            return Ok(Some(format!(
+                "Running low: {}× {} left in stock",
+                remaining_stock,
+                escape(&product.name)
+            )));
+        }
+
+        Ok(None)
+    }
+
+    /// Refuses purchases and transfers that would bring the balance of the sender below
+    /// their credit limit. A personal limit takes precedence over the configured one.
+    fn check_credit_limit(&self, price: Rappen, sender: &User) -> Result<(), Error> {
+        let credit_limit = match self
+            .user_service
+            .get_credit_limit(sender)?
+            .or(self.config.credit_limit)
+        {
+            Some(credit_limit) => credit_limit,
+            None => return Ok(()),
+        };
+
+        let balance = self.balance_service.get_balance(sender)?;
+        if balance - price < -credit_limit {
+            return Err(Error::Validation(format!(
+                "This would exceed your credit limit of {}, your current balance is {}",
+                self.currency_formatter.format_amount(credit_limit),
+                self.currency_formatter.format_amount(balance)
+            )));
+        }
+
+        Ok(())
+    }
+
+    fn handle_amount(
+        &self,
+        amount: Rappen,
+        kind: TransactionKind,
+        sender: &User,
+    ) -> Result<Response, Error> {
+        if kind == TransactionKind::Purchase {
+            self.check_credit_limit(-amount, sender)?;
+        }
+
         self.transaction_service
-            .register_amount_transaction(amount, sender)?;
+            .register_amount_transaction(amount, kind, sender)?;
 
         Ok(Response {
             contents: format!("Recorded {}", self.currency_formatter.format_amount(amount)),
+            attachment: None,
         })
     }
 
+    fn handle_transfer(
+        &self,
+        recipient_name: &str,
+        amount: Rappen,
+        sender: &User,
+    ) -> Result<Vec<Response>, Error> {
+        if amount <= 0 {
+            return Err(Error::Validation(
+                "Transferred amounts must be positive".to_string(),
+            ));
+        }
+
+        let recipient = self.user_service.get_user_with_name(recipient_name)?;
+        if recipient == *sender {
+            return Err(Error::Validation(
+                "You can't transfer money to yourself".to_string(),
+            ));
+        }
+
+        self.check_credit_limit(amount, sender)?;
+
+        self.transaction_service
+            .register_transfer(amount, sender, &recipient)?;
+
+        let balances = self
+            .balance_service
+            .get_balances()?
+            .into_iter()
+            .filter(|balance| balance.user_id == sender.id || balance.user_id == recipient.id)
+            .collect::<Vec<_>>();
+
+        Ok(vec![
+            Response {
+                contents: format!(
+                    "Transferred {} to {}",
+                    self.currency_formatter.format_amount(amount),
+                    escape(&recipient.name)
+                ),
+                attachment: None,
+            },
+            Response {
+                contents: self.format_balances(&balances, sender),
+                attachment: None,
+            },
+        ])
+    }
+
     fn format_products(&self, products: &[Product]) -> String {
         let message_header = "Available products:";
         let message_body = products
             .iter()
             .map(|product| {
-                format!(
+                let text = format!(
                     "/{} - {} ({})",
-                    product.identifier,
-                    product.name,
+                    escape(&product.identifier),
+                    escape(&product.name),
                     self.currency_formatter.format_amount(product.price)
+                );
+
+                match product.stock {
+                    Some(stock) if stock <= 0 => format!("{} - out of stock", text),
+                    Some(stock) if stock <= self.config.low_stock_threshold => {
+                        format!("{} - {} left", text, stock)
+                    }
+                    _ => text,
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        format!("{}\n{}", message_header, message_body)
+    }
+
+    fn format_transactions(&self, transactions: &[Transaction]) -> String {
+        if transactions.is_empty() {
+            return "No transactions recorded".to_string();
+        }
+
+        let message_header = "Last transactions:";
+        let message_body = transactions
+            .iter()
+            .map(|transaction| {
+                format!(
+                    "{} - {} ({})",
+                    transaction.timestamp.format("%d.%m.%Y %H:%M"),
+                    escape(&transaction.description()),
+                    self.currency_formatter.format_amount(transaction.amount)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        format!("{}\n{}", message_header, message_body)
+    }
+
+    fn format_price_history(&self, product: &Product, prices: &[ProductPrice]) -> String {
+        if prices.is_empty() {
+            return format!("No prices recorded for {}", escape(&product.name));
+        }
+
+        let message_header = format!("Prices of {}:", escape(&product.name));
+        let message_body = prices
+            .iter()
+            .map(|price| {
+                format!(
+                    "{} - {}",
+                    price.valid_from.format("%d.%m.%Y"),
+                    self.currency_formatter.format_amount(price.price)
                 )
             })
             .collect::<Vec<_>>()
@@ -145,9 +666,9 @@ impl<'a> MessageHandlerImpl<'a> {
                 );
 
                 if balance.user_id == sender.id {
-                    format!("**{}**", text)
+                    bold(&text)
                 } else {
-                    text
+                    escape(&text)
                 }
             })
             .collect::<Vec<_>>()
@@ -157,32 +678,59 @@ impl<'a> MessageHandlerImpl<'a> {
     }
 }
 
+fn validate_price(price: Rappen) -> Result<(), Error> {
+    if price < 0 {
+        return Err(Error::Validation("Prices must not be negative".to_string()));
+    }
+
+    Ok(())
+}
+
 impl MessageHandler for MessageHandlerImpl<'_> {
     fn handle_message(&self, message: &Message) -> Vec<Response> {
-        match self.message_router.route_message(message) {
-            Err(_) => vec![Response {
-                contents: "Internal error (1)".to_string(),
-            }],
-            Ok(None) => vec![Response {
-                contents: "Invalid input".to_string(),
-            }],
-            Ok(Some(message_action)) => self
-                .handle_message_action(message_action, &message.sender)
-                .unwrap_or_else(|_| {
-                    vec![Response {
-                        contents: "Internal error (4)".to_string(),
-                    }]
-                }),
-        }
+        let result = self
+            .message_router
+            .route_message(message)
+            .and_then(|message_action| match message_action {
+                Some(message_action) => self.handle_message_action(message_action, &message.sender),
+                None => Ok(vec![Response {
+                    contents: "Invalid input".to_string(),
+                    attachment: None,
+                }]),
+            });
+
+        result.unwrap_or_else(|error| {
+            let contents = if error.is_user_error() {
+                log::debug!("Rejected message from {}: {}", message.sender.id, error);
+                escape(&error.to_string())
+            } else {
+                log::error!(
+                    "Failed to handle message from {}: {}",
+                    message.sender.id,
+                    error
+                );
+                "Internal error".to_string()
+            };
+
+            vec![Response {
+                contents,
+                attachment: None,
+            }]
+        })
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use chrono::NaiveDate;
+
     use crate::currency_handling::currency_formatter::CurrencyFormatterMock;
     use crate::message_router::MessageRouterMock;
+    use crate::models::{BalanceQuery, CashPosition, Statement};
+    use crate::qr_bill::Creditor;
     use crate::services::balance_service::BalanceServiceMock;
     use crate::services::product_service::ProductServiceMock;
+    use crate::services::statement_service::StatementServiceMock;
     use crate::services::transaction_service::TransactionServiceMock;
     use crate::services::user_service::UserServiceMock;
 
@@ -201,7 +749,77 @@ mod tests {
             Box::new(ProductServiceMock::new()),
             Box::new(TransactionServiceMock::new()),
             Box::new(BalanceServiceMock::new()),
+            Box::new(StatementServiceMock::new()),
+            Box::new(CurrencyFormatterMock::new()),
+            Config::default(),
+        );
+
+        let responses = message_handler.handle_message(&Message {
+            sender: User {
+                id: "some id".to_string(),
+                name: "foo".to_string(),
+            },
+            contents: "bar".to_string(),
+        });
+        assert_eq!(
+            vec![Response {
+                contents: "Invalid input".to_string(),
+                attachment: None,
+            }],
+            responses
+        );
+    }
+
+    #[test]
+    fn internal_error_is_not_shown_to_user() {
+        let mut message_router = MessageRouterMock::new();
+        message_router
+            .expect_route_message(|arg| arg.any())
+            .returns_once(Err(Error::Database(diesel::result::Error::NotFound)));
+
+        let message_handler = MessageHandlerImpl::new(
+            Box::new(message_router),
+            Box::new(UserServiceMock::new()),
+            Box::new(ProductServiceMock::new()),
+            Box::new(TransactionServiceMock::new()),
+            Box::new(BalanceServiceMock::new()),
+            Box::new(StatementServiceMock::new()),
+            Box::new(CurrencyFormatterMock::new()),
+            Config::default(),
+        );
+
+        let responses = message_handler.handle_message(&Message {
+            sender: User {
+                id: "some id".to_string(),
+                name: "foo".to_string(),
+            },
+            contents: "bar".to_string(),
+        });
+        assert_eq!(
+            vec![Response {
+                contents: "Internal error".to_string(),
+                attachment: None,
+            }],
+            responses
+        );
+    }
+
+    #[test]
+    fn user_error_is_shown_to_user() {
+        let mut message_router = MessageRouterMock::new();
+        message_router
+            .expect_route_message(|arg| arg.any())
+            .returns_once(Err(Error::Validation("Amount is too large".to_string())));
+
+        let message_handler = MessageHandlerImpl::new(
+            Box::new(message_router),
+            Box::new(UserServiceMock::new()),
+            Box::new(ProductServiceMock::new()),
+            Box::new(TransactionServiceMock::new()),
+            Box::new(BalanceServiceMock::new()),
+            Box::new(StatementServiceMock::new()),
             Box::new(CurrencyFormatterMock::new()),
+            Config::default(),
         );
 
         let responses = message_handler.handle_message(&Message {
@@ -213,7 +831,8 @@ mod tests {
         });
         assert_eq!(
             vec![Response {
-                contents: "Invalid input".to_string()
+                contents: "Amount is too large".to_string(),
+                attachment: None,
             }],
             responses
         );
@@ -254,11 +873,15 @@ mod tests {
                     identifier: "coke".to_string(),
                     name: "a coke".to_string(),
                     price: 420,
+                    available: true,
+                    stock: None,
                 },
                 Product {
                     identifier: "energy".to_string(),
                     name: "energy drink".to_string(),
                     price: 50,
+                    available: true,
+                    stock: Some(0),
                 },
             ]));
 
@@ -268,7 +891,9 @@ mod tests {
             Box::new(product_service),
             Box::new(TransactionServiceMock::new()),
             Box::new(BalanceServiceMock::new()),
+            Box::new(StatementServiceMock::new()),
             Box::new(currency_formatter),
+            Config::default(),
         );
 
         let responses = message_handler.handle_message(&Message {
@@ -279,8 +904,1596 @@ mod tests {
         assert_eq!(
             vec![Response {
                 contents:
-                    "Available products:\n/coke - a coke (4.20)\n/energy - energy drink (0.50)"
-                        .to_string()
+                    "Available products:\n/coke - a coke (4.20)\n/energy - energy drink (0.50) - out of stock"
+                        .to_string(),
+                attachment: None,
+            }],
+            responses
+        );
+    }
+
+    #[test]
+    fn balances_command_escapes_names() {
+        let mut message_router = MessageRouterMock::new();
+        message_router
+            .expect_route_message(|arg| arg.any())
+            .returns_once(Ok(Some(MessageAction::Command(
+                Command::GetCurrentBalances(BalanceQuery::default()),
+            ))));
+
+        let user = User {
+            id: "some id".to_string(),
+            name: "foo_bar*".to_string(),
+        };
+
+        let mut user_service = UserServiceMock::new();
+        user_service
+            .expect_update_user(|arg| arg.partial_eq_owned(user.clone()))
+            .returns_once(Ok(()));
+
+        let mut balance_service = BalanceServiceMock::new();
+        balance_service
+            .expect_query_balances(|arg| arg.partial_eq_owned(BalanceQuery::default()))
+            .returns_once(Ok(vec![
+                Balance {
+                    user_id: user.id.clone(),
+                    name: user.name.clone(),
+                    amount: 0,
+                },
+                Balance {
+                    user_id: "other id".to_string(),
+                    name: "*Anna* [x]".to_string(),
+                    amount: 0,
+                },
+            ]));
+
+        let mut currency_formatter = CurrencyFormatterMock::new();
+        currency_formatter
+            .expect_format_amount(|arg| arg.partial_eq(0))
+            .times(2)
+            .returns("0.-".to_string());
+
+        let message_handler = MessageHandlerImpl::new(
+            Box::new(message_router),
+            Box::new(user_service),
+            Box::new(ProductServiceMock::new()),
+            Box::new(TransactionServiceMock::new()),
+            Box::new(balance_service),
+            Box::new(StatementServiceMock::new()),
+            Box::new(currency_formatter),
+            Config::default(),
+        );
+
+        let responses = message_handler.handle_message(&Message {
+            sender: user,
+            contents: "/balance".to_string(),
+        });
+
+        assert_eq!(
+            vec![Response {
+                contents: "Current stats:\n*- foo*\\_*bar*\\** (0.-)*\n- \\*Anna\\* \\[x] (0.-)"
+                    .to_string(),
+                attachment: None,
+            }],
+            responses
+        );
+    }
+
+    #[test]
+    fn undo_command() {
+        let mut message_router = MessageRouterMock::new();
+        message_router
+            .expect_route_message(|arg| arg.any())
+            .returns_once(Ok(Some(MessageAction::Command(Command::Undo))));
+
+        let user = User {
+            id: "some id".to_string(),
+            name: "foo".to_string(),
+        };
+
+        let mut user_service = UserServiceMock::new();
+        user_service
+            .expect_update_user(|arg| arg.partial_eq_owned(user.clone()))
+            .returns_once(Ok(()));
+
+        let transaction = Transaction {
+            id: 1,
+            amount: -420,
+            timestamp: Utc::now().naive_utc(),
+            user: user.id.clone(),
+            product_name: Some("a coke".to_string()),
+            counterparty: None,
+            product_identifier: None,
+            product_price: None,
+            quantity: None,
+            import_hash: None,
+            kind: TransactionKind::Purchase,
+        };
+
+        let mut transaction_service = TransactionServiceMock::new();
+        transaction_service
+            .expect_get_last_transaction(|arg| arg.partial_eq_owned(user.clone()))
+            .returns_once(Ok(Some(transaction.clone())));
+        transaction_service
+            .expect_delete_transaction(|arg| arg.partial_eq_owned(transaction))
+            .returns_once(Ok(()));
+
+        let mut balance_service = BalanceServiceMock::new();
+        balance_service
+            .expect_get_balances()
+            .returns_once(Ok(vec![Balance {
+                user_id: user.id.clone(),
+                name: user.name.clone(),
+                amount: 0,
+            }]));
+
+        let mut currency_formatter = CurrencyFormatterMock::new();
+        currency_formatter
+            .expect_format_amount(|arg| arg.partial_eq(420))
+            .returns_once("4.20".to_string());
+        currency_formatter
+            .expect_format_amount(|arg| arg.partial_eq(0))
+            .returns_once("0.-".to_string());
+
+        let message_handler = MessageHandlerImpl::new(
+            Box::new(message_router),
+            Box::new(user_service),
+            Box::new(ProductServiceMock::new()),
+            Box::new(transaction_service),
+            Box::new(balance_service),
+            Box::new(StatementServiceMock::new()),
+            Box::new(currency_formatter),
+            Config::default(),
+        );
+
+        let responses = message_handler.handle_message(&Message {
+            sender: user,
+            contents: "/undo".to_string(),
+        });
+
+        assert_eq!(
+            vec![
+                Response {
+                    contents: "Reverted a coke (4.20)".to_string(),
+                    attachment: None,
+                },
+                Response {
+                    contents: "Current stats:\n*- foo (0.-)*".to_string(),
+                    attachment: None,
+                }
+            ],
+            responses
+        );
+    }
+
+    #[test]
+    fn undo_command_outside_of_time_window() {
+        let mut message_router = MessageRouterMock::new();
+        message_router
+            .expect_route_message(|arg| arg.any())
+            .returns_once(Ok(Some(MessageAction::Command(Command::Undo))));
+
+        let user = User {
+            id: "some id".to_string(),
+            name: "foo".to_string(),
+        };
+
+        let mut user_service = UserServiceMock::new();
+        user_service
+            .expect_update_user(|arg| arg.partial_eq_owned(user.clone()))
+            .returns_once(Ok(()));
+
+        let mut transaction_service = TransactionServiceMock::new();
+        transaction_service
+            .expect_get_last_transaction(|arg| arg.partial_eq_owned(user.clone()))
+            .returns_once(Ok(Some(Transaction {
+                id: 1,
+                amount: 1000,
+                timestamp: Utc::now().naive_utc() - Duration::hours(1),
+                user: user.id.clone(),
+                product_name: None,
+                counterparty: None,
+                product_identifier: None,
+                product_price: None,
+                quantity: None,
+                import_hash: None,
+                kind: TransactionKind::Deposit,
+            })));
+
+        let message_handler = MessageHandlerImpl::new(
+            Box::new(message_router),
+            Box::new(user_service),
+            Box::new(ProductServiceMock::new()),
+            Box::new(transaction_service),
+            Box::new(BalanceServiceMock::new()),
+            Box::new(StatementServiceMock::new()),
+            Box::new(CurrencyFormatterMock::new()),
+            Config::default(),
+        );
+
+        let responses = message_handler.handle_message(&Message {
+            sender: user,
+            contents: "/undo".to_string(),
+        });
+
+        assert_eq!(
+            vec![Response {
+                contents: "Only transactions from the last 5 minutes can be undone".to_string(),
+                attachment: None,
+            }],
+            responses
+        );
+    }
+
+    #[test]
+    fn undo_command_refuses_corrections() {
+        let mut message_router = MessageRouterMock::new();
+        message_router
+            .expect_route_message(|arg| arg.any())
+            .returns_once(Ok(Some(MessageAction::Command(Command::Undo))));
+
+        let user = User {
+            id: "some id".to_string(),
+            name: "foo".to_string(),
+        };
+
+        let mut user_service = UserServiceMock::new();
+        user_service
+            .expect_update_user(|arg| arg.partial_eq_owned(user.clone()))
+            .returns_once(Ok(()));
+
+        let mut transaction_service = TransactionServiceMock::new();
+        transaction_service
+            .expect_get_last_transaction(|arg| arg.partial_eq_owned(user.clone()))
+            .returns_once(Ok(Some(Transaction {
+                id: 1,
+                amount: -500,
+                timestamp: Utc::now().naive_utc(),
+                user: user.id.clone(),
+                product_name: None,
+                counterparty: None,
+                product_identifier: None,
+                product_price: None,
+                quantity: None,
+                import_hash: None,
+                kind: TransactionKind::Correction,
+            })));
+
+        let message_handler = MessageHandlerImpl::new(
+            Box::new(message_router),
+            Box::new(user_service),
+            Box::new(ProductServiceMock::new()),
+            Box::new(transaction_service),
+            Box::new(BalanceServiceMock::new()),
+            Box::new(StatementServiceMock::new()),
+            Box::new(CurrencyFormatterMock::new()),
+            Config::default(),
+        );
+
+        let responses = message_handler.handle_message(&Message {
+            sender: user,
+            contents: "/undo".to_string(),
+        });
+
+        assert_eq!(
+            vec![Response {
+                contents: "Balance corrections can only be changed by an admin".to_string(),
+                attachment: None,
+            }],
+            responses
+        );
+    }
+
+    #[test]
+    fn history_command() {
+        let mut message_router = MessageRouterMock::new();
+        message_router
+            .expect_route_message(|arg| arg.any())
+            .returns_once(Ok(Some(MessageAction::Command(
+                Command::GetTransactionHistory(2),
+            ))));
+
+        let user = User {
+            id: "some id".to_string(),
+            name: "foo".to_string(),
+        };
+
+        let mut user_service = UserServiceMock::new();
+        user_service
+            .expect_update_user(|arg| arg.partial_eq_owned(user.clone()))
+            .returns_once(Ok(()));
+
+        let mut transaction_service = TransactionServiceMock::new();
+        transaction_service
+            .expect_get_last_transactions(
+                |arg| arg.partial_eq_owned(user.clone()),
+                |arg| arg.partial_eq(2),
+            )
+            .returns_once(Ok(vec![
+                Transaction {
+                    id: 2,
+                    amount: -420,
+                    timestamp: NaiveDate::from_ymd_opt(2019, 10, 6)
+                        .unwrap()
+                        .and_hms_opt(9, 15, 0)
+                        .unwrap(),
+                    user: user.id.clone(),
+                    product_name: Some("a coke".to_string()),
+                    counterparty: None,
+                    product_identifier: None,
+                    product_price: None,
+                    quantity: None,
+                    import_hash: None,
+                    kind: TransactionKind::Purchase,
+                },
+                Transaction {
+                    id: 1,
+                    amount: 1000,
+                    timestamp: NaiveDate::from_ymd_opt(2019, 10, 5)
+                        .unwrap()
+                        .and_hms_opt(17, 30, 0)
+                        .unwrap(),
+                    user: user.id.clone(),
+                    product_name: None,
+                    counterparty: None,
+                    product_identifier: None,
+                    product_price: None,
+                    quantity: None,
+                    import_hash: None,
+                    kind: TransactionKind::Correction,
+                },
+            ]));
+
+        let mut currency_formatter = CurrencyFormatterMock::new();
+        currency_formatter
+            .expect_format_amount(|arg| arg.partial_eq(-420))
+            .returns_once("- 4.20".to_string());
+        currency_formatter
+            .expect_format_amount(|arg| arg.partial_eq(1000))
+            .returns_once("10.-".to_string());
+
+        let message_handler = MessageHandlerImpl::new(
+            Box::new(message_router),
+            Box::new(user_service),
+            Box::new(ProductServiceMock::new()),
+            Box::new(transaction_service),
+            Box::new(BalanceServiceMock::new()),
+            Box::new(StatementServiceMock::new()),
+            Box::new(currency_formatter),
+            Config::default(),
+        );
+
+        let responses = message_handler.handle_message(&Message {
+            sender: user,
+            contents: "/history 2".to_string(),
+        });
+
+        assert_eq!(
+            vec![Response {
+                contents: "Last transactions:\n\
+                           06.10.2019 09:15 - a coke (- 4.20)\n\
+                           05.10.2019 17:30 - correction (10.-)"
+                    .to_string(),
+                attachment: None,
+            }],
+            responses
+        );
+    }
+
+    #[test]
+    fn product_with_quantity() {
+        let user = User {
+            id: "some id".to_string(),
+            name: "foo".to_string(),
+        };
+
+        let product = Product {
+            identifier: "coke".to_string(),
+            name: "a coke".to_string(),
+            price: 420,
+            available: true,
+            stock: None,
+        };
+
+        let mut message_router = MessageRouterMock::new();
+        message_router
+            .expect_route_message(|arg| arg.any())
+            .returns_once(Ok(Some(MessageAction::Product {
+                product: product.clone(),
+                quantity: 3,
+            })));
+
+        let mut user_service = UserServiceMock::new();
+        user_service
+            .expect_update_user(|arg| arg.partial_eq_owned(user.clone()))
+            .returns_once(Ok(()));
+        user_service
+            .expect_get_credit_limit(|arg| arg.partial_eq_owned(user.clone()))
+            .returns_once(Ok(None));
+
+        let mut transaction_service = TransactionServiceMock::new();
+        transaction_service
+            .expect_register_product_transaction(
+                |arg| arg.partial_eq_owned(product),
+                |arg| arg.partial_eq(3),
+                |arg| arg.partial_eq_owned(user.clone()),
+            )
+            .returns_once(Ok(()));
+
+        let mut balance_service = BalanceServiceMock::new();
+        balance_service
+            .expect_get_balances()
+            .returns_once(Ok(vec![Balance {
+                user_id: user.id.clone(),
+                name: user.name.clone(),
+                amount: -1260,
+            }]));
+
+        let mut currency_formatter = CurrencyFormatterMock::new();
+        currency_formatter
+            .expect_format_amount(|arg| arg.partial_eq(1260))
+            .returns_once("12.60".to_string());
+        currency_formatter
+            .expect_format_amount(|arg| arg.partial_eq(-1260))
+            .returns_once("- 12.60".to_string());
+
+        let mut product_service = ProductServiceMock::new();
+        product_service
+            .expect_get_price_at(|arg| arg.partial_eq("coke"), |arg| arg.any())
+            .returns_once(Ok(Some(420)));
+
+        let message_handler = MessageHandlerImpl::new(
+            Box::new(message_router),
+            Box::new(user_service),
+            Box::new(product_service),
+            Box::new(transaction_service),
+            Box::new(balance_service),
+            Box::new(StatementServiceMock::new()),
+            Box::new(currency_formatter),
+            Config::default(),
+        );
+
+        let responses = message_handler.handle_message(&Message {
+            sender: user,
+            contents: "3x coke".to_string(),
+        });
+
+        assert_eq!(
+            vec![
+                Response {
+                    contents: "Recorded 3× a coke (12.60)".to_string(),
+                    attachment: None,
+                },
+                Response {
+                    contents: "Current stats:\n*- foo (- 12.60)*".to_string(),
+                    attachment: None,
+                }
+            ],
+            responses
+        );
+    }
+
+    #[test]
+    fn product_out_of_stock_is_refused() {
+        let user = User {
+            id: "some id".to_string(),
+            name: "foo".to_string(),
+        };
+
+        let mut message_router = MessageRouterMock::new();
+        message_router
+            .expect_route_message(|arg| arg.any())
+            .returns_once(Ok(Some(MessageAction::Product {
+                product: Product {
+                    identifier: "coke".to_string(),
+                    name: "a coke".to_string(),
+                    price: 420,
+                    available: true,
+                    stock: Some(2),
+                },
+                quantity: 3,
+            })));
+
+        let mut user_service = UserServiceMock::new();
+        user_service
+            .expect_update_user(|arg| arg.partial_eq_owned(user.clone()))
+            .returns_once(Ok(()));
+
+        let message_handler = MessageHandlerImpl::new(
+            Box::new(message_router),
+            Box::new(user_service),
+            Box::new(ProductServiceMock::new()),
+            Box::new(TransactionServiceMock::new()),
+            Box::new(BalanceServiceMock::new()),
+            Box::new(StatementServiceMock::new()),
+            Box::new(CurrencyFormatterMock::new()),
+            Config {
+                out_of_stock_policy: OutOfStockPolicy::Refuse,
+                ..Config::default()
+            },
+        );
+
+        let responses = message_handler.handle_message(&Message {
+            sender: user,
+            contents: "3x coke".to_string(),
+        });
+
+        assert_eq!(
+            vec![Response {
+                contents: "Only 2× a coke left in stock".to_string(),
+                attachment: None,
+            }],
+            responses
+        );
+    }
+
+    #[test]
+    fn product_running_low_is_reported() {
+        let user = User {
+            id: "some id".to_string(),
+            name: "foo".to_string(),
+        };
+
+        let product = Product {
+            identifier: "coke".to_string(),
+            name: "a coke".to_string(),
+            price: 420,
+            available: true,
+            stock: Some(4),
+        };
+
+        let mut message_router = MessageRouterMock::new();
+        message_router
+            .expect_route_message(|arg| arg.any())
+            .returns_once(Ok(Some(MessageAction::Product {
+                product: product.clone(),
+                quantity: 1,
+            })));
+
+        let mut user_service = UserServiceMock::new();
+        user_service
+            .expect_update_user(|arg| arg.partial_eq_owned(user.clone()))
+            .returns_once(Ok(()));
+        user_service
+            .expect_get_credit_limit(|arg| arg.partial_eq_owned(user.clone()))
+            .returns_once(Ok(None));
+
+        let mut transaction_service = TransactionServiceMock::new();
+        transaction_service
+            .expect_register_product_transaction(
+                |arg| arg.partial_eq_owned(product),
+                |arg| arg.partial_eq(1),
+                |arg| arg.partial_eq_owned(user.clone()),
+            )
+            .returns_once(Ok(()));
+
+        let mut balance_service = BalanceServiceMock::new();
+        balance_service
+            .expect_get_balances()
+            .returns_once(Ok(vec![]));
+
+        let mut currency_formatter = CurrencyFormatterMock::new();
+        currency_formatter
+            .expect_format_amount(|arg| arg.partial_eq(420))
+            .returns_once("4.20".to_string());
+
+        let mut product_service = ProductServiceMock::new();
+        product_service
+            .expect_get_price_at(|arg| arg.partial_eq("coke"), |arg| arg.any())
+            .returns_once(Ok(Some(420)));
+
+        let message_handler = MessageHandlerImpl::new(
+            Box::new(message_router),
+            Box::new(user_service),
+            Box::new(product_service),
+            Box::new(transaction_service),
+            Box::new(balance_service),
+            Box::new(StatementServiceMock::new()),
+            Box::new(currency_formatter),
+            Config::default(),
+        );
+
+        let responses = message_handler.handle_message(&Message {
+            sender: user,
+            contents: "coke".to_string(),
+        });
+
+        assert_eq!(
+            "Recorded a coke (4.20)\nRunning low: 3× a coke left in stock",
+            responses[0].contents
+        );
+    }
+
+    #[test]
+    fn product_over_credit_limit_is_refused() {
+        let user = User {
+            id: "some id".to_string(),
+            name: "foo".to_string(),
+        };
+
+        let mut message_router = MessageRouterMock::new();
+        message_router
+            .expect_route_message(|arg| arg.any())
+            .returns_once(Ok(Some(MessageAction::Product {
+                product: Product {
+                    identifier: "coke".to_string(),
+                    name: "a coke".to_string(),
+                    price: 420,
+                    available: true,
+                    stock: None,
+                },
+                quantity: 1,
+            })));
+
+        let mut user_service = UserServiceMock::new();
+        user_service
+            .expect_update_user(|arg| arg.partial_eq_owned(user.clone()))
+            .returns_once(Ok(()));
+        user_service
+            .expect_get_credit_limit(|arg| arg.partial_eq_owned(user.clone()))
+            .returns_once(Ok(None));
+
+        let mut balance_service = BalanceServiceMock::new();
+        balance_service
+            .expect_get_balance(|arg| arg.partial_eq_owned(user.clone()))
+            .returns_once(Ok(-4800));
+
+        let mut currency_formatter = CurrencyFormatterMock::new();
+        currency_formatter
+            .expect_format_amount(|arg| arg.partial_eq(5000))
+            .returns_once("50.-".to_string());
+        currency_formatter
+            .expect_format_amount(|arg| arg.partial_eq(-4800))
+            .returns_once("- 48.-".to_string());
+
+        let mut product_service = ProductServiceMock::new();
+        product_service
+            .expect_get_price_at(|arg| arg.partial_eq("coke"), |arg| arg.any())
+            .returns_once(Ok(Some(420)));
+
+        let message_handler = MessageHandlerImpl::new(
+            Box::new(message_router),
+            Box::new(user_service),
+            Box::new(product_service),
+            Box::new(TransactionServiceMock::new()),
+            Box::new(balance_service),
+            Box::new(StatementServiceMock::new()),
+            Box::new(currency_formatter),
+            Config {
+                credit_limit: Some(5000),
+                ..Config::default()
+            },
+        );
+
+        let responses = message_handler.handle_message(&Message {
+            sender: user,
+            contents: "coke".to_string(),
+        });
+
+        assert_eq!(
+            vec![Response {
+                contents: "This would exceed your credit limit of 50.-, \
+                           your current balance is - 48.-"
+                    .to_string(),
+                attachment: None,
+            }],
+            responses
+        );
+    }
+
+    #[test]
+    fn product_credit_limit_uses_effective_price() {
+        let user = User {
+            id: "some id".to_string(),
+            name: "foo".to_string(),
+        };
+
+        let mut message_router = MessageRouterMock::new();
+        message_router
+            .expect_route_message(|arg| arg.any())
+            .returns_once(Ok(Some(MessageAction::Product {
+                product: Product {
+                    identifier: "coke".to_string(),
+                    name: "a coke".to_string(),
+                    price: 100,
+                    available: true,
+                    stock: None,
+                },
+                quantity: 1,
+            })));
+
+        let mut user_service = UserServiceMock::new();
+        user_service
+            .expect_update_user(|arg| arg.partial_eq_owned(user.clone()))
+            .returns_once(Ok(()));
+        user_service
+            .expect_get_credit_limit(|arg| arg.partial_eq_owned(user.clone()))
+            .returns_once(Ok(Some(5000)));
+
+        let mut product_service = ProductServiceMock::new();
+        product_service
+            .expect_get_price_at(|arg| arg.partial_eq("coke"), |arg| arg.any())
+            .returns_once(Ok(Some(420)));
+
+        let mut balance_service = BalanceServiceMock::new();
+        balance_service
+            .expect_get_balance(|arg| arg.partial_eq_owned(user.clone()))
+            .returns_once(Ok(-4800));
+
+        let mut currency_formatter = CurrencyFormatterMock::new();
+        currency_formatter
+            .expect_format_amount(|arg| arg.partial_eq(5000))
+            .returns_once("50.-".to_string());
+        currency_formatter
+            .expect_format_amount(|arg| arg.partial_eq(-4800))
+            .returns_once("- 48.-".to_string());
+
+        let message_handler = MessageHandlerImpl::new(
+            Box::new(message_router),
+            Box::new(user_service),
+            Box::new(product_service),
+            Box::new(TransactionServiceMock::new()),
+            Box::new(balance_service),
+            Box::new(StatementServiceMock::new()),
+            Box::new(currency_formatter),
+            Config::default(),
+        );
+
+        let responses = message_handler.handle_message(&Message {
+            sender: user,
+            contents: "coke".to_string(),
+        });
+
+        assert_eq!(
+            vec![Response {
+                contents: "This would exceed your credit limit of 50.-, \
+                           your current balance is - 48.-"
+                    .to_string(),
+                attachment: None,
+            }],
+            responses
+        );
+    }
+
+    #[test]
+    fn negative_amount_over_credit_limit_is_refused() {
+        let user = User {
+            id: "some id".to_string(),
+            name: "foo".to_string(),
+        };
+
+        let mut message_router = MessageRouterMock::new();
+        message_router
+            .expect_route_message(|arg| arg.any())
+            .returns_once(Ok(Some(MessageAction::Amount {
+                amount: -500,
+                kind: TransactionKind::Purchase,
+            })));
+
+        let mut user_service = UserServiceMock::new();
+        user_service
+            .expect_update_user(|arg| arg.partial_eq_owned(user.clone()))
+            .returns_once(Ok(()));
+        user_service
+            .expect_get_credit_limit(|arg| arg.partial_eq_owned(user.clone()))
+            .returns_once(Ok(None));
+
+        let mut balance_service = BalanceServiceMock::new();
+        balance_service
+            .expect_get_balance(|arg| arg.partial_eq_owned(user.clone()))
+            .returns_once(Ok(-4800));
+
+        let mut currency_formatter = CurrencyFormatterMock::new();
+        currency_formatter
+            .expect_format_amount(|arg| arg.partial_eq(5000))
+            .returns_once("50.-".to_string());
+        currency_formatter
+            .expect_format_amount(|arg| arg.partial_eq(-4800))
+            .returns_once("- 48.-".to_string());
+
+        let message_handler = MessageHandlerImpl::new(
+            Box::new(message_router),
+            Box::new(user_service),
+            Box::new(ProductServiceMock::new()),
+            Box::new(TransactionServiceMock::new()),
+            Box::new(balance_service),
+            Box::new(StatementServiceMock::new()),
+            Box::new(currency_formatter),
+            Config {
+                credit_limit: Some(5000),
+                ..Config::default()
+            },
+        );
+
+        let responses = message_handler.handle_message(&Message {
+            sender: user,
+            contents: "-5".to_string(),
+        });
+
+        assert_eq!(
+            vec![Response {
+                contents: "This would exceed your credit limit of 50.-, \
+                           your current balance is - 48.-"
+                    .to_string(),
+                attachment: None,
+            }],
+            responses
+        );
+    }
+
+    #[test]
+    fn topup_command() {
+        let user = User {
+            id: "1234".to_string(),
+            name: "foo".to_string(),
+        };
+
+        let mut message_router = MessageRouterMock::new();
+        message_router
+            .expect_route_message(|arg| arg.any())
+            .returns_once(Ok(Some(MessageAction::Command(Command::TopUp(None)))));
+
+        let mut user_service = UserServiceMock::new();
+        user_service
+            .expect_update_user(|arg| arg.partial_eq_owned(user.clone()))
+            .returns_once(Ok(()));
+
+        let mut balance_service = BalanceServiceMock::new();
+        balance_service
+            .expect_get_balance(|arg| arg.partial_eq_owned(user.clone()))
+            .returns_once(Ok(-1250));
+
+        let mut currency_formatter = CurrencyFormatterMock::new();
+        currency_formatter
+            .expect_format_amount(|arg| arg.partial_eq(1250))
+            .returns_once("12.50".to_string());
+
+        let message_handler = MessageHandlerImpl::new(
+            Box::new(message_router),
+            Box::new(user_service),
+            Box::new(ProductServiceMock::new()),
+            Box::new(TransactionServiceMock::new()),
+            Box::new(balance_service),
+            Box::new(StatementServiceMock::new()),
+            Box::new(currency_formatter),
+            Config {
+                creditor: Some(Creditor {
+                    iban: "CH4431999123000889012".to_string(),
+                    name: "Kafi Kässeli".to_string(),
+                    street: None,
+                    building_number: None,
+                    postal_code: "8001".to_string(),
+                    town: "Zürich".to_string(),
+                    country: "CH".to_string(),
+                }),
+                ..Config::default()
+            },
+        );
+
+        let responses = message_handler.handle_message(&Message {
+            sender: user,
+            contents: "/topup".to_string(),
+        });
+
+        assert_eq!(1, responses.len());
+        assert_eq!(
+            "Please pay 12.50 to CH44 3199 9123 0008 8901 2 (Kafi Kässeli)\n\
+             Reference: 000000000000000000000012347",
+            responses[0].contents
+        );
+        let attachment = responses[0].attachment.as_ref().unwrap();
+        assert_eq!("topup.png", attachment.file_name);
+        assert_eq!(b"\x89PNG", &attachment.data[..4]);
+    }
+
+    #[test]
+    fn expense_command() {
+        let user = User {
+            id: "some id".to_string(),
+            name: "foo".to_string(),
+        };
+
+        let mut message_router = MessageRouterMock::new();
+        message_router
+            .expect_route_message(|arg| arg.any())
+            .returns_once(Ok(Some(MessageAction::Command(Command::RecordExpense {
+                amount: 2490,
+                description: "coffee beans".to_string(),
+            }))));
+
+        let mut user_service = UserServiceMock::new();
+        user_service
+            .expect_update_user(|arg| arg.partial_eq_owned(user.clone()))
+            .returns_once(Ok(()));
+
+        let mut transaction_service = TransactionServiceMock::new();
+        transaction_service
+            .expect_register_expense(
+                |arg| arg.partial_eq(2490),
+                |arg| arg.partial_eq("coffee beans"),
+                |arg| arg.partial_eq_owned(user.clone()),
+            )
+            .returns_once(Ok(()));
+
+        let mut balance_service = BalanceServiceMock::new();
+        balance_service
+            .expect_get_balances()
+            .returns_once(Ok(vec![Balance {
+                user_id: user.id.clone(),
+                name: user.name.clone(),
+                amount: 2490,
+            }]));
+
+        let mut currency_formatter = CurrencyFormatterMock::new();
+        currency_formatter
+            .expect_format_amount(|arg| arg.partial_eq(2490))
+            .times(2)
+            .returns("24.90".to_string());
+
+        let message_handler = MessageHandlerImpl::new(
+            Box::new(message_router),
+            Box::new(user_service),
+            Box::new(ProductServiceMock::new()),
+            Box::new(transaction_service),
+            Box::new(balance_service),
+            Box::new(StatementServiceMock::new()),
+            Box::new(currency_formatter),
+            Config::default(),
+        );
+
+        let responses = message_handler.handle_message(&Message {
+            sender: user,
+            contents: "/expense 24.90 coffee beans".to_string(),
+        });
+
+        assert_eq!(
+            vec![
+                Response {
+                    contents: "Recorded expense for coffee beans (24.90)".to_string(),
+                    attachment: None,
+                },
+                Response {
+                    contents: "Current stats:\n*- foo (24.90)*".to_string(),
+                    attachment: None,
+                }
+            ],
+            responses
+        );
+    }
+
+    #[test]
+    fn cash_command() {
+        let user = User {
+            id: "some id".to_string(),
+            name: "foo".to_string(),
+        };
+
+        let mut message_router = MessageRouterMock::new();
+        message_router
+            .expect_route_message(|arg| arg.any())
+            .returns_once(Ok(Some(MessageAction::Command(Command::GetCashPosition))));
+
+        let mut user_service = UserServiceMock::new();
+        user_service
+            .expect_update_user(|arg| arg.partial_eq_owned(user.clone()))
+            .returns_once(Ok(()));
+
+        let mut balance_service = BalanceServiceMock::new();
+        balance_service
+            .expect_get_cash_position()
+            .returns_once(Ok(CashPosition {
+                deposits: 10000,
+                expenses: 2500,
+                balances: 6000,
+            }));
+
+        let mut currency_formatter = CurrencyFormatterMock::new();
+        for (amount, formatted) in &[
+            (7500, "75.-"),
+            (10000, "100.-"),
+            (2500, "25.-"),
+            (6000, "60.-"),
+            (1500, "15.-"),
+        ] {
+            currency_formatter
+                .expect_format_amount(move |arg| arg.partial_eq(*amount))
+                .returns_once(formatted.to_string());
+        }
+
+        let message_handler = MessageHandlerImpl::new(
+            Box::new(message_router),
+            Box::new(user_service),
+            Box::new(ProductServiceMock::new()),
+            Box::new(TransactionServiceMock::new()),
+            Box::new(balance_service),
+            Box::new(StatementServiceMock::new()),
+            Box::new(currency_formatter),
+            Config::default(),
+        );
+
+        let responses = message_handler.handle_message(&Message {
+            sender: user,
+            contents: "/cash".to_string(),
+        });
+
+        assert_eq!(
+            "Cash: 75.-\nDeposits: 100.-\nExpenses: 25.-\nUser balances: 60.-\nSurplus: 15.-",
+            responses[0].contents
+        );
+    }
+
+    #[test]
+    fn restock_command() {
+        let user = User {
+            id: "some id".to_string(),
+            name: "foo".to_string(),
+        };
+
+        let mut message_router = MessageRouterMock::new();
+        message_router
+            .expect_route_message(|arg| arg.any())
+            .returns_once(Ok(Some(MessageAction::Command(Command::Restock {
+                product_identifier: "coke".to_string(),
+                quantity: 24,
+            }))));
+
+        let mut user_service = UserServiceMock::new();
+        user_service
+            .expect_update_user(|arg| arg.partial_eq_owned(user.clone()))
+            .returns_once(Ok(()));
+
+        let mut product_service = ProductServiceMock::new();
+        product_service
+            .expect_restock_product(|arg| arg.partial_eq("coke"), |arg| arg.partial_eq(24))
+            .returns_once(Ok(Product {
+                identifier: "coke".to_string(),
+                name: "a coke".to_string(),
+                price: 420,
+                available: true,
+                stock: Some(26),
+            }));
+
+        let message_handler = MessageHandlerImpl::new(
+            Box::new(message_router),
+            Box::new(user_service),
+            Box::new(product_service),
+            Box::new(TransactionServiceMock::new()),
+            Box::new(BalanceServiceMock::new()),
+            Box::new(StatementServiceMock::new()),
+            Box::new(CurrencyFormatterMock::new()),
+            Config {
+                admins: vec![user.id.clone()],
+                ..Config::default()
+            },
+        );
+
+        let responses = message_handler.handle_message(&Message {
+            sender: user,
+            contents: "/restock coke 24".to_string(),
+        });
+
+        assert_eq!(
+            vec![Response {
+                contents: "Restocked a coke, 26 in stock".to_string(),
+                attachment: None,
+            }],
+            responses
+        );
+    }
+
+    #[test]
+    fn admin_command_is_rejected_for_members() {
+        let user = User {
+            id: "some id".to_string(),
+            name: "foo".to_string(),
+        };
+
+        let mut message_router = MessageRouterMock::new();
+        message_router
+            .expect_route_message(|arg| arg.any())
+            .returns_once(Ok(Some(MessageAction::Command(Command::Restock {
+                product_identifier: "coke".to_string(),
+                quantity: 24,
+            }))));
+
+        let mut user_service = UserServiceMock::new();
+        user_service
+            .expect_update_user(|arg| arg.partial_eq_owned(user.clone()))
+            .returns_once(Ok(()));
+        user_service
+            .expect_get_role(|arg| arg.partial_eq_owned(user.clone()))
+            .returns_once(Ok(Role::Member));
+
+        let message_handler = MessageHandlerImpl::new(
+            Box::new(message_router),
+            Box::new(user_service),
+            Box::new(ProductServiceMock::new()),
+            Box::new(TransactionServiceMock::new()),
+            Box::new(BalanceServiceMock::new()),
+            Box::new(StatementServiceMock::new()),
+            Box::new(CurrencyFormatterMock::new()),
+            Config::default(),
+        );
+
+        let responses = message_handler.handle_message(&Message {
+            sender: user,
+            contents: "/restock coke 24".to_string(),
+        });
+
+        assert_eq!(
+            vec![Response {
+                contents: "Only admins are allowed to use this command".to_string(),
+                attachment: None,
+            }],
+            responses
+        );
+    }
+
+    #[test]
+    fn correct_balance_command() {
+        let admin = User {
+            id: "admin id".to_string(),
+            name: "foo".to_string(),
+        };
+        let user = User {
+            id: "some id".to_string(),
+            name: "Anna".to_string(),
+        };
+
+        let mut message_router = MessageRouterMock::new();
+        message_router
+            .expect_route_message(|arg| arg.any())
+            .returns_once(Ok(Some(MessageAction::Command(Command::CorrectBalance {
+                user: "anna".to_string(),
+                amount: -500,
+            }))));
+
+        let mut user_service = UserServiceMock::new();
+        user_service
+            .expect_update_user(|arg| arg.partial_eq_owned(admin.clone()))
+            .returns_once(Ok(()));
+        user_service
+            .expect_get_role(|arg| arg.partial_eq_owned(admin.clone()))
+            .returns_once(Ok(Role::Admin));
+        user_service
+            .expect_get_user_with_name(|arg| arg.partial_eq("anna"))
+            .returns_once(Ok(user.clone()));
+
+        let mut transaction_service = TransactionServiceMock::new();
+        transaction_service
+            .expect_register_amount_transaction(
+                |arg| arg.partial_eq(-500),
+                |arg| arg.partial_eq(TransactionKind::Correction),
+                |arg| arg.partial_eq_owned(user.clone()),
+            )
+            .returns_once(Ok(()));
+
+        let mut balance_service = BalanceServiceMock::new();
+        balance_service
+            .expect_get_balances()
+            .returns_once(Ok(vec![Balance {
+                user_id: user.id.clone(),
+                name: user.name.clone(),
+                amount: -500,
+            }]));
+
+        let mut currency_formatter = CurrencyFormatterMock::new();
+        currency_formatter
+            .expect_format_amount(|arg| arg.partial_eq(-500))
+            .times(2)
+            .returns("- 5.00".to_string());
+
+        let message_handler = MessageHandlerImpl::new(
+            Box::new(message_router),
+            Box::new(user_service),
+            Box::new(ProductServiceMock::new()),
+            Box::new(transaction_service),
+            Box::new(balance_service),
+            Box::new(StatementServiceMock::new()),
+            Box::new(currency_formatter),
+            Config::default(),
+        );
+
+        let responses = message_handler.handle_message(&Message {
+            sender: admin,
+            contents: "/correct anna -5".to_string(),
+        });
+
+        assert_eq!(
+            vec![
+                Response {
+                    contents: "Corrected the balance of Anna by - 5.00".to_string(),
+                    attachment: None,
+                },
+                Response {
+                    contents: "Current stats:\n- Anna (- 5.00)".to_string(),
+                    attachment: None,
+                }
+            ],
+            responses
+        );
+    }
+
+    #[test]
+    fn set_price_command() {
+        let user = User {
+            id: "some id".to_string(),
+            name: "foo".to_string(),
+        };
+
+        let mut message_router = MessageRouterMock::new();
+        message_router
+            .expect_route_message(|arg| arg.any())
+            .returns_once(Ok(Some(MessageAction::Command(Command::SetPrice {
+                product_identifier: "coke".to_string(),
+                price: 220,
+            }))));
+
+        let mut user_service = UserServiceMock::new();
+        user_service
+            .expect_update_user(|arg| arg.partial_eq_owned(user.clone()))
+            .returns_once(Ok(()));
+        user_service
+            .expect_get_role(|arg| arg.partial_eq_owned(user.clone()))
+            .returns_once(Ok(Role::Admin));
+
+        let mut product_service = ProductServiceMock::new();
+        product_service
+            .expect_set_price(|arg| arg.partial_eq("coke"), |arg| arg.partial_eq(220))
+            .returns_once(Ok(Product {
+                identifier: "coke".to_string(),
+                name: "a coke".to_string(),
+                price: 220,
+                available: true,
+                stock: None,
+            }));
+
+        let mut currency_formatter = CurrencyFormatterMock::new();
+        currency_formatter
+            .expect_format_amount(|arg| arg.partial_eq(220))
+            .returns_once("2.20".to_string());
+
+        let message_handler = MessageHandlerImpl::new(
+            Box::new(message_router),
+            Box::new(user_service),
+            Box::new(product_service),
+            Box::new(TransactionServiceMock::new()),
+            Box::new(BalanceServiceMock::new()),
+            Box::new(StatementServiceMock::new()),
+            Box::new(currency_formatter),
+            Config::default(),
+        );
+
+        let responses = message_handler.handle_message(&Message {
+            sender: user,
+            contents: "/setprice coke 2.20".to_string(),
+        });
+
+        assert_eq!(
+            vec![Response {
+                contents: "a coke now costs 2.20".to_string(),
+                attachment: None,
+            }],
+            responses
+        );
+    }
+
+    #[test]
+    fn prices_command() {
+        let user = User {
+            id: "some id".to_string(),
+            name: "foo".to_string(),
+        };
+        let day = |day| {
+            NaiveDate::from_ymd_opt(2026, 9, day)
+                .unwrap()
+                .and_hms_opt(8, 0, 0)
+                .unwrap()
+        };
+
+        let mut message_router = MessageRouterMock::new();
+        message_router
+            .expect_route_message(|arg| arg.any())
+            .returns_once(Ok(Some(MessageAction::Command(Command::GetPriceHistory(
+                "coffee".to_string(),
+            )))));
+
+        let mut user_service = UserServiceMock::new();
+        user_service
+            .expect_update_user(|arg| arg.partial_eq_owned(user.clone()))
+            .returns_once(Ok(()));
+
+        let mut product_service = ProductServiceMock::new();
+        product_service
+            .expect_get_product_with_identifier(|arg| arg.partial_eq("coffee"))
+            .returns_once(Ok(Some(Product {
+                identifier: "coffee".to_string(),
+                name: "Coffee".to_string(),
+                price: 180,
+                available: true,
+                stock: None,
+            })));
+        product_service
+            .expect_get_price_history(|arg| arg.partial_eq("coffee"))
+            .returns_once(Ok(vec![
+                ProductPrice {
+                    id: 1,
+                    product_identifier: "coffee".to_string(),
+                    price: 150,
+                    valid_from: day(1),
+                    valid_until: Some(day(15)),
+                },
+                ProductPrice {
+                    id: 2,
+                    product_identifier: "coffee".to_string(),
+                    price: 180,
+                    valid_from: day(15),
+                    valid_until: None,
+                },
+            ]));
+
+        let mut currency_formatter = CurrencyFormatterMock::new();
+        currency_formatter
+            .expect_format_amount(|arg| arg.partial_eq(150))
+            .returns_once("1.50".to_string());
+        currency_formatter
+            .expect_format_amount(|arg| arg.partial_eq(180))
+            .returns_once("1.80".to_string());
+
+        let message_handler = MessageHandlerImpl::new(
+            Box::new(message_router),
+            Box::new(user_service),
+            Box::new(product_service),
+            Box::new(TransactionServiceMock::new()),
+            Box::new(BalanceServiceMock::new()),
+            Box::new(StatementServiceMock::new()),
+            Box::new(currency_formatter),
+            Config::default(),
+        );
+
+        let responses = message_handler.handle_message(&Message {
+            sender: user,
+            contents: "/prices coffee".to_string(),
+        });
+
+        assert_eq!(
+            vec![Response {
+                contents: "Prices of Coffee:\n01.09.2026 - 1.50\n15.09.2026 - 1.80".to_string(),
+                attachment: None,
+            }],
+            responses
+        );
+    }
+
+    #[test]
+    fn statement_command() {
+        let user = User {
+            id: "some id".to_string(),
+            name: "foo".to_string(),
+        };
+        let month = NaiveDate::from_ymd_opt(2026, 9, 1).unwrap();
+
+        let mut message_router = MessageRouterMock::new();
+        message_router
+            .expect_route_message(|arg| arg.any())
+            .returns_once(Ok(Some(MessageAction::Command(Command::GetStatement(
+                Some(month),
+            )))));
+
+        let mut user_service = UserServiceMock::new();
+        user_service
+            .expect_update_user(|arg| arg.partial_eq_owned(user.clone()))
+            .returns_once(Ok(()));
+
+        let mut statement_service = StatementServiceMock::new();
+        statement_service
+            .expect_get_statement(
+                |arg| arg.partial_eq_owned(user.clone()),
+                |arg| arg.partial_eq(month),
+            )
+            .returns_once(Ok(Statement {
+                user: user.clone(),
+                month,
+                opening_balance: 0,
+                transactions: Vec::new(),
+                deposits: 0,
+                consumption: 0,
+                closing_balance: 0,
+            }));
+
+        let message_handler = MessageHandlerImpl::new(
+            Box::new(message_router),
+            Box::new(user_service),
+            Box::new(ProductServiceMock::new()),
+            Box::new(TransactionServiceMock::new()),
+            Box::new(BalanceServiceMock::new()),
+            Box::new(statement_service),
+            Box::new(CurrencyFormatterImpl),
+            Config::default(),
+        );
+
+        let responses = message_handler.handle_message(&Message {
+            sender: user,
+            contents: "/statement 2026-09".to_string(),
+        });
+
+        assert_eq!(
+            vec![Response {
+                contents: "*Statement for foo, September 2026*\n\
+                           Opening balance: 0.-\n\
+                           No transactions recorded\n\
+                           *Deposits:* 0.-\n\
+                           *Consumption:* 0.-\n\
+                           *Closing balance:* 0.-"
+                    .to_string(),
+                attachment: None,
+            }],
+            responses
+        );
+    }
+
+    #[test]
+    fn transfer() {
+        let sender = User {
+            id: "some id".to_string(),
+            name: "foo".to_string(),
+        };
+        let recipient = User {
+            id: "other id".to_string(),
+            name: "Anna".to_string(),
+        };
+
+        let mut message_router = MessageRouterMock::new();
+        message_router
+            .expect_route_message(|arg| arg.any())
+            .returns_once(Ok(Some(MessageAction::Transfer {
+                recipient: "anna".to_string(),
+                amount: 250,
+            })));
+
+        let mut user_service = UserServiceMock::new();
+        user_service
+            .expect_update_user(|arg| arg.partial_eq_owned(sender.clone()))
+            .returns_once(Ok(()));
+        user_service
+            .expect_get_user_with_name(|arg| arg.partial_eq("anna"))
+            .returns_once(Ok(recipient.clone()));
+        user_service
+            .expect_get_credit_limit(|arg| arg.partial_eq_owned(sender.clone()))
+            .returns_once(Ok(None));
+
+        let mut transaction_service = TransactionServiceMock::new();
+        transaction_service
+            .expect_register_transfer(
+                |arg| arg.partial_eq(250),
+                |arg| arg.partial_eq_owned(sender.clone()),
+                |arg| arg.partial_eq_owned(recipient.clone()),
+            )
+            .returns_once(Ok(()));
+
+        let mut balance_service = BalanceServiceMock::new();
+        balance_service.expect_get_balances().returns_once(Ok(vec![
+            Balance {
+                user_id: sender.id.clone(),
+                name: sender.name.clone(),
+                amount: -250,
+            },
+            Balance {
+                user_id: "third id".to_string(),
+                name: "bar".to_string(),
+                amount: 1000,
+            },
+            Balance {
+                user_id: recipient.id.clone(),
+                name: recipient.name.clone(),
+                amount: 250,
+            },
+        ]));
+
+        let mut currency_formatter = CurrencyFormatterMock::new();
+        currency_formatter
+            .expect_format_amount(|arg| arg.partial_eq(250))
+            .times(2)
+            .returns("2.50".to_string());
+        currency_formatter
+            .expect_format_amount(|arg| arg.partial_eq(-250))
+            .returns_once("- 2.50".to_string());
+
+        let message_handler = MessageHandlerImpl::new(
+            Box::new(message_router),
+            Box::new(user_service),
+            Box::new(ProductServiceMock::new()),
+            Box::new(transaction_service),
+            Box::new(balance_service),
+            Box::new(StatementServiceMock::new()),
+            Box::new(currency_formatter),
+            Config::default(),
+        );
+
+        let responses = message_handler.handle_message(&Message {
+            sender,
+            contents: "/pay @anna 2.50".to_string(),
+        });
+
+        assert_eq!(
+            vec![
+                Response {
+                    contents: "Transferred 2.50 to Anna".to_string(),
+                    attachment: None,
+                },
+                Response {
+                    contents: "Current stats:\n*- foo (- 2.50)*\n- Anna (2.50)".to_string(),
+                    attachment: None,
+                }
+            ],
+            responses
+        );
+    }
+
+    #[test]
+    fn transfer_over_credit_limit_is_refused() {
+        let sender = User {
+            id: "some id".to_string(),
+            name: "foo".to_string(),
+        };
+
+        let mut message_router = MessageRouterMock::new();
+        message_router
+            .expect_route_message(|arg| arg.any())
+            .returns_once(Ok(Some(MessageAction::Transfer {
+                recipient: "anna".to_string(),
+                amount: 1000,
+            })));
+
+        let mut user_service = UserServiceMock::new();
+        user_service
+            .expect_update_user(|arg| arg.partial_eq_owned(sender.clone()))
+            .returns_once(Ok(()));
+        user_service
+            .expect_get_user_with_name(|arg| arg.partial_eq("anna"))
+            .returns_once(Ok(User {
+                id: "other id".to_string(),
+                name: "Anna".to_string(),
+            }));
+        user_service
+            .expect_get_credit_limit(|arg| arg.partial_eq_owned(sender.clone()))
+            .returns_once(Ok(Some(2000)));
+
+        let mut balance_service = BalanceServiceMock::new();
+        balance_service
+            .expect_get_balance(|arg| arg.partial_eq_owned(sender.clone()))
+            .returns_once(Ok(-1500));
+
+        let mut currency_formatter = CurrencyFormatterMock::new();
+        currency_formatter
+            .expect_format_amount(|arg| arg.partial_eq(2000))
+            .returns_once("20.-".to_string());
+        currency_formatter
+            .expect_format_amount(|arg| arg.partial_eq(-1500))
+            .returns_once("- 15.-".to_string());
+
+        let message_handler = MessageHandlerImpl::new(
+            Box::new(message_router),
+            Box::new(user_service),
+            Box::new(ProductServiceMock::new()),
+            Box::new(TransactionServiceMock::new()),
+            Box::new(balance_service),
+            Box::new(StatementServiceMock::new()),
+            Box::new(currency_formatter),
+            Config::default(),
+        );
+
+        let responses = message_handler.handle_message(&Message {
+            sender,
+            contents: "/pay @anna 10".to_string(),
+        });
+
+        assert_eq!(
+            vec![Response {
+                contents: "This would exceed your credit limit of 20.-, \
+                           your current balance is - 15.-"
+                    .to_string(),
+                attachment: None,
             }],
             responses
         );
@@ -1,13 +1,22 @@
+use chrono::NaiveDate;
 #[cfg(test)]
 use mockiato::mockable;
 
 use crate::currency_handling::currency_parser::CurrencyParser;
-use crate::models::{Command, Message, MessageAction, Product};
+use crate::error::Error;
+use crate::models::{
+    BalanceOrder, BalanceQuery, Command, Message, MessageAction, Product, Role, TransactionKind,
+};
 use crate::services::product_service::ProductService;
 
+const DEFAULT_HISTORY_LENGTH: u32 = 10;
+const MAX_HISTORY_LENGTH: u32 = 50;
+const MAX_PRODUCT_QUANTITY: u32 = 100;
+const ACTIVE_USER_DAYS: u32 = 30;
+
 #[cfg_attr(test, mockable)]
 pub trait MessageRouter {
-    fn route_message(&self, message: &Message) -> Result<Option<MessageAction>, ()>;
+    fn route_message(&self, message: &Message) -> Result<Option<MessageAction>, Error>;
 }
 
 pub struct MessageRouterImpl<'a> {
@@ -27,33 +36,295 @@ impl<'a> MessageRouterImpl<'a> {
     }
 
     fn get_command(&self, message: &Message) -> Option<Command> {
-        match message.contents.as_ref() {
-            "/list" => Some(Command::ListAvailableItems),
-            "/stats" => Some(Command::GetCurrentBalances),
+        let words = split_arguments(&message.contents);
+        let (command, arguments) = words.split_first()?;
+        let arguments = arguments.iter().map(String::as_str).collect::<Vec<_>>();
+
+        match (command.as_str(), arguments.as_slice()) {
+            ("/list", []) => Some(Command::ListAvailableItems),
+            ("/stats", options) => {
+                get_balance_query(options, message).map(Command::GetCurrentBalances)
+            }
+            ("/history", []) => Some(Command::GetTransactionHistory(DEFAULT_HISTORY_LENGTH)),
+            ("/history", [count]) => count
+                .parse::<u32>()
+                .ok()
+                .filter(|count| *count > 0)
+                .map(|count| Command::GetTransactionHistory(count.min(MAX_HISTORY_LENGTH))),
+            ("/undo", []) => Some(Command::Undo),
+            ("/topup", []) => Some(Command::TopUp(None)),
+            ("/topup", [amount]) => self
+                .currency_parser
+                .parse_text(amount)
+                .ok()
+                .filter(|amount| *amount > 0)
+                .map(|amount| Command::TopUp(Some(amount))),
+            ("/expense", [amount, description @ ..]) if !description.is_empty() => self
+                .currency_parser
+                .parse_text(amount)
+                .ok()
+                .filter(|amount| *amount > 0)
+                .map(|amount| Command::RecordExpense {
+                    amount,
+                    description: description.join(" "),
+                }),
+            ("/cash", []) => Some(Command::GetCashPosition),
+            ("/statement", []) => Some(Command::GetStatement(None)),
+            ("/statement", [month]) => {
+                NaiveDate::parse_from_str(&format!("{}-01", month), "%Y-%m-%d")
+                    .ok()
+                    .map(|month| Command::GetStatement(Some(month)))
+            }
+            ("/prices", [product_identifier]) => Some(Command::GetPriceHistory(
+                product_identifier.trim_start_matches('/').to_lowercase(),
+            )),
+            ("/restock", [product_identifier, quantity]) => quantity
+                .parse::<u32>()
+                .ok()
+                .filter(|quantity| *quantity > 0)
+                .map(|quantity| Command::Restock {
+                    product_identifier: product_identifier.trim_start_matches('/').to_lowercase(),
+                    quantity,
+                }),
+            ("/correct", [user @ .., amount]) if !user.is_empty() => self
+                .currency_parser
+                .parse_text(amount)
+                .ok()
+                .map(|amount| Command::CorrectBalance {
+                    user: get_user_name(user),
+                    amount,
+                }),
+            ("/promote", user) if !user.is_empty() => Some(Command::SetRole {
+                user: get_user_name(user),
+                role: Role::Admin,
+            }),
+            ("/demote", user) if !user.is_empty() => Some(Command::SetRole {
+                user: get_user_name(user),
+                role: Role::Member,
+            }),
+            ("/setlimit", [user @ .., limit]) if !user.is_empty() => {
+                if limit.eq_ignore_ascii_case("none") {
+                    Some(Command::SetCreditLimit {
+                        user: get_user_name(user),
+                        limit: None,
+                    })
+                } else {
+                    self.currency_parser
+                        .parse_text(limit)
+                        .ok()
+                        .filter(|limit| *limit >= 0)
+                        .map(|limit| Command::SetCreditLimit {
+                            user: get_user_name(user),
+                            limit: Some(limit),
+                        })
+                }
+            }
+            ("/addproduct", [identifier, name @ .., price]) if !name.is_empty() => self
+                .currency_parser
+                .parse_text(price)
+                .ok()
+                .map(|price| Command::AddProduct {
+                    identifier: identifier.trim_start_matches('/').to_lowercase(),
+                    name: name.join(" "),
+                    price,
+                }),
+            ("/setprice", [product_identifier, price]) => self
+                .currency_parser
+                .parse_text(price)
+                .ok()
+                .map(|price| Command::SetPrice {
+                    product_identifier: product_identifier.trim_start_matches('/').to_lowercase(),
+                    price,
+                }),
+            ("/rename", [product_identifier, name @ ..]) if !name.is_empty() => {
+                Some(Command::RenameProduct {
+                    product_identifier: product_identifier.trim_start_matches('/').to_lowercase(),
+                    name: name.join(" "),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    fn get_transfer(&self, message: &Message) -> Option<MessageAction> {
+        let words = split_arguments(&message.contents);
+        let words = words.iter().map(String::as_str).collect::<Vec<_>>();
+
+        match words.as_slice() {
+            ["/pay", recipient @ .., amount] if !recipient.is_empty() => {
+                let recipient = get_user_name(recipient);
+                let amount = self.currency_parser.parse_text(amount).ok()?;
+
+                Some(MessageAction::Transfer { recipient, amount })
+            }
             _ => None,
         }
     }
 
-    fn get_product(&self, message: &Message) -> Result<Option<Product>, ()> {
-        let product_identifier = message.contents.trim_start_matches('/').to_lowercase();
+    fn get_product(&self, message: &Message) -> Result<Option<(Product, u32)>, Error> {
+        let text = message.contents.trim_start_matches('/').to_lowercase();
+
+        let mut product = None;
+        for (product_identifier, quantity) in split_quantity(&text)
+            .into_iter()
+            .chain(std::iter::once((text.as_str(), 1)))
+        {
+            if let Some(found_product) = self
+                .product_service
+                .get_product_with_identifier(product_identifier)?
+            {
+                product = Some((found_product, quantity));
+                break;
+            }
+        }
+
+        let (product, quantity) = match product {
+            Some(product) => product,
+            None => return Ok(None),
+        };
+
+        if !product.available {
+            return Err(Error::Validation(format!(
+                "{} is no longer available",
+                product.name
+            )));
+        }
+
+        if quantity == 0 || quantity > MAX_PRODUCT_QUANTITY {
+            return Err(Error::Validation(format!(
+                "Quantity must be between 1 and {}",
+                MAX_PRODUCT_QUANTITY
+            )));
+        }
+
+        Ok(Some((product, quantity)))
+    }
+}
+
+/// Splits a command into its words. Text in double quotes is kept together as a single word.
+fn split_arguments(text: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut is_quoted = false;
+    let mut is_in_word = false;
+
+    for chr in text.chars() {
+        match chr {
+            // Phones tend to replace straight quotes with typographic ones
+            '"' | '“' | '”' => {
+                is_quoted = !is_quoted;
+                is_in_word = true;
+            }
+            chr if chr.is_whitespace() && !is_quoted => {
+                if is_in_word {
+                    words.push(std::mem::take(&mut word));
+                    is_in_word = false;
+                }
+            }
+            chr => {
+                word.push(chr);
+                is_in_word = true;
+            }
+        }
+    }
+
+    if is_in_word {
+        words.push(word);
+    }
+
+    words
+}
+
+/// Parses the options of `/stats`, e.g. `/stats debt nonzero` or `/stats me`.
+fn get_balance_query(options: &[&str], message: &Message) -> Option<BalanceQuery> {
+    let mut query = BalanceQuery::default();
+
+    for option in options {
+        match option.to_lowercase().as_ref() {
+            "me" => query.user_id = Some(message.sender.id.clone()),
+            "name" => query.order = BalanceOrder::Name,
+            "debt" => query.order = BalanceOrder::Amount,
+            "nonzero" => query.hide_zero_balances = true,
+            "active" => query.active_within_days = Some(ACTIVE_USER_DAYS),
+            _ => return None,
+        }
+    }
+
+    Some(query)
+}
+
+/// Joins the words of a user name, `@Anna Muster` becomes `Anna Muster`.
+fn get_user_name(words: &[&str]) -> String {
+    words.join(" ").trim_start_matches('@').to_string()
+}
+
+/// Splits `3x coke`, `coke 3` or `coke*3` into the product identifier and the quantity.
+/// Identifiers can contain an `x` themselves, so every possible split is returned.
+fn split_quantity(text: &str) -> Vec<(&str, u32)> {
+    let text = text.trim();
+    let mut splits = Vec::new();
+
+    let quantity_end = text
+        .find(|chr: char| !chr.is_ascii_digit())
+        .unwrap_or(text.len());
+    let (quantity, rest) = text.split_at(quantity_end);
+    let rest = rest.trim_start();
+    if let Some(identifier) = rest.strip_prefix(['x', '×']) {
+        splits.extend(parse_quantity_parts(identifier, quantity));
+        // `2xl` might also be two of `xl`
+        if !identifier.starts_with(char::is_whitespace) {
+            splits.extend(parse_quantity_parts(rest, quantity));
+        }
+    }
+
+    splits.extend(
+        text.split_once('*')
+            .and_then(|(identifier, quantity)| parse_quantity_parts(identifier, quantity)),
+    );
+    splits.extend(
+        text.rsplit_once(' ')
+            .and_then(|(identifier, quantity)| parse_quantity_parts(identifier, quantity)),
+    );
 
-        self.product_service
-            .get_product_with_identifier(&product_identifier)
+    splits
+}
+
+fn parse_quantity_parts<'a>(identifier: &'a str, quantity: &str) -> Option<(&'a str, u32)> {
+    let identifier = identifier.trim();
+    let quantity = quantity.trim();
+
+    if identifier.is_empty() || !quantity.chars().all(|chr| chr.is_ascii_digit()) {
+        return None;
     }
+
+    quantity
+        .parse::<u32>()
+        .ok()
+        .map(|quantity| (identifier, quantity))
 }
 
 impl<'a> MessageRouter for MessageRouterImpl<'a> {
-    fn route_message(&self, message: &Message) -> Result<Option<MessageAction>, ()> {
+    fn route_message(&self, message: &Message) -> Result<Option<MessageAction>, Error> {
         if let Some(command) = self.get_command(message) {
             return Ok(Some(MessageAction::Command(command)));
         }
 
-        if let Some(product) = self.get_product(message)? {
-            return Ok(Some(MessageAction::Product(product)));
+        if let Some(transfer) = self.get_transfer(message) {
+            return Ok(Some(transfer));
+        }
+
+        if let Some((product, quantity)) = self.get_product(message)? {
+            return Ok(Some(MessageAction::Product { product, quantity }));
         }
 
         if let Ok(amount) = self.currency_parser.parse_text(&message.contents) {
-            return Ok(Some(MessageAction::Amount(amount)));
+            // Negative amounts pay for things that aren't on the list
+            let kind = if amount < 0 {
+                TransactionKind::Purchase
+            } else {
+                TransactionKind::Deposit
+            };
+            return Ok(Some(MessageAction::Amount { amount, kind }));
         }
 
         Ok(None)
@@ -74,13 +345,13 @@ mod tests {
         product_service
             .expect_get_product_with_identifier(|arg| arg.partial_eq("foo"))
             .times(1)
-            .returns(Ok(None));
+            .returns_once(Ok(None));
 
         let mut currency_parser = CurrencyParserMock::new();
         currency_parser
             .expect_parse_text(|arg| arg.partial_eq("Foo"))
             .times(1)
-            .returns(Err(()));
+            .returns_once(Err(Error::Parse("Foo".to_string())));
 
         let message = Message {
             sender: User {
@@ -114,7 +385,66 @@ mod tests {
 
         let action = router.route_message(&message).unwrap();
         assert_eq!(
-            Some(MessageAction::Command(Command::GetCurrentBalances)),
+            Some(MessageAction::Command(Command::GetCurrentBalances(
+                BalanceQuery::default()
+            ))),
+            action
+        );
+    }
+
+    #[test]
+    fn stats_command_with_options() {
+        let product_service = ProductServiceMock::new();
+
+        let currency_parser = CurrencyParserMock::new();
+
+        let message = Message {
+            sender: User {
+                id: "some id".to_string(),
+                name: "Test".to_string(),
+            },
+            contents: "/stats debt nonzero".to_string(),
+        };
+
+        let router = MessageRouterImpl::new(Box::new(product_service), Box::new(currency_parser));
+
+        let action = router.route_message(&message).unwrap();
+        assert_eq!(
+            Some(MessageAction::Command(Command::GetCurrentBalances(
+                BalanceQuery {
+                    order: BalanceOrder::Amount,
+                    hide_zero_balances: true,
+                    ..BalanceQuery::default()
+                }
+            ))),
+            action
+        );
+    }
+
+    #[test]
+    fn stats_command_for_sender() {
+        let product_service = ProductServiceMock::new();
+
+        let currency_parser = CurrencyParserMock::new();
+
+        let message = Message {
+            sender: User {
+                id: "some id".to_string(),
+                name: "Test".to_string(),
+            },
+            contents: "/stats me".to_string(),
+        };
+
+        let router = MessageRouterImpl::new(Box::new(product_service), Box::new(currency_parser));
+
+        let action = router.route_message(&message).unwrap();
+        assert_eq!(
+            Some(MessageAction::Command(Command::GetCurrentBalances(
+                BalanceQuery {
+                    user_id: Some("some id".to_string()),
+                    ..BalanceQuery::default()
+                }
+            ))),
             action
         );
     }
@@ -143,18 +473,31 @@ mod tests {
     }
 
     #[test]
-    fn known_product() {
-        let product = Product {
-            identifier: "foo".to_string(),
-            name: "test product".to_string(),
-            price: 60,
+    fn history_command() {
+        let product_service = ProductServiceMock::new();
+
+        let currency_parser = CurrencyParserMock::new();
+
+        let message = Message {
+            sender: User {
+                id: "some id".to_string(),
+                name: "Test".to_string(),
+            },
+            contents: "/history".to_string(),
         };
 
-        let mut product_service = ProductServiceMock::new();
-        product_service
-            .expect_get_product_with_identifier(|arg| arg.partial_eq("foo"))
-            .times(1)
-            .returns(Ok(Some(product.clone())));
+        let router = MessageRouterImpl::new(Box::new(product_service), Box::new(currency_parser));
+
+        let action = router.route_message(&message).unwrap();
+        assert_eq!(
+            Some(MessageAction::Command(Command::GetTransactionHistory(10))),
+            action
+        );
+    }
+
+    #[test]
+    fn history_command_with_count() {
+        let product_service = ProductServiceMock::new();
 
         let currency_parser = CurrencyParserMock::new();
 
@@ -163,28 +506,148 @@ mod tests {
                 id: "some id".to_string(),
                 name: "Test".to_string(),
             },
-            contents: "/foo".to_string(),
+            contents: "/history 3".to_string(),
         };
 
         let router = MessageRouterImpl::new(Box::new(product_service), Box::new(currency_parser));
 
         let action = router.route_message(&message).unwrap();
-        assert_eq!(Some(MessageAction::Product(product)), action);
+        assert_eq!(
+            Some(MessageAction::Command(Command::GetTransactionHistory(3))),
+            action
+        );
     }
 
     #[test]
-    fn known_product_without_slash() {
-        let product = Product {
-            identifier: "foo".to_string(),
-            name: "test product".to_string(),
-            price: 60,
+    fn undo_command() {
+        let product_service = ProductServiceMock::new();
+
+        let currency_parser = CurrencyParserMock::new();
+
+        let message = Message {
+            sender: User {
+                id: "some id".to_string(),
+                name: "Test".to_string(),
+            },
+            contents: "/undo".to_string(),
         };
 
-        let mut product_service = ProductServiceMock::new();
-        product_service
-            .expect_get_product_with_identifier(|arg| arg.partial_eq("foo"))
+        let router = MessageRouterImpl::new(Box::new(product_service), Box::new(currency_parser));
+
+        let action = router.route_message(&message).unwrap();
+        assert_eq!(Some(MessageAction::Command(Command::Undo)), action);
+    }
+
+    #[test]
+    fn restock_command() {
+        let product_service = ProductServiceMock::new();
+
+        let currency_parser = CurrencyParserMock::new();
+
+        let message = Message {
+            sender: User {
+                id: "some id".to_string(),
+                name: "Test".to_string(),
+            },
+            contents: "/restock Coke 24".to_string(),
+        };
+
+        let router = MessageRouterImpl::new(Box::new(product_service), Box::new(currency_parser));
+
+        let action = router.route_message(&message).unwrap();
+        assert_eq!(
+            Some(MessageAction::Command(Command::Restock {
+                product_identifier: "coke".to_string(),
+                quantity: 24
+            })),
+            action
+        );
+    }
+
+    #[test]
+    fn restock_command_with_slash() {
+        let product_service = ProductServiceMock::new();
+
+        let currency_parser = CurrencyParserMock::new();
+
+        let message = Message {
+            sender: User {
+                id: "some id".to_string(),
+                name: "Test".to_string(),
+            },
+            contents: "/restock /coke 24".to_string(),
+        };
+
+        let router = MessageRouterImpl::new(Box::new(product_service), Box::new(currency_parser));
+
+        let action = router.route_message(&message).unwrap();
+        assert_eq!(
+            Some(MessageAction::Command(Command::Restock {
+                product_identifier: "coke".to_string(),
+                quantity: 24
+            })),
+            action
+        );
+    }
+
+    #[test]
+    fn prices_command() {
+        let product_service = ProductServiceMock::new();
+
+        let currency_parser = CurrencyParserMock::new();
+
+        let message = Message {
+            sender: User {
+                id: "some id".to_string(),
+                name: "Test".to_string(),
+            },
+            contents: "/prices /Coffee".to_string(),
+        };
+
+        let router = MessageRouterImpl::new(Box::new(product_service), Box::new(currency_parser));
+
+        let action = router.route_message(&message).unwrap();
+        assert_eq!(
+            Some(MessageAction::Command(Command::GetPriceHistory(
+                "coffee".to_string()
+            ))),
+            action
+        );
+    }
+
+    #[test]
+    fn correct_balance_command() {
+        let product_service = ProductServiceMock::new();
+
+        let mut currency_parser = CurrencyParserMock::new();
+        currency_parser
+            .expect_parse_text(|arg| arg.partial_eq("-5"))
             .times(1)
-            .returns(Ok(Some(product.clone())));
+            .returns_once(Ok(-500));
+
+        let message = Message {
+            sender: User {
+                id: "some id".to_string(),
+                name: "Test".to_string(),
+            },
+            contents: "/correct @Anna Muster -5".to_string(),
+        };
+
+        let router = MessageRouterImpl::new(Box::new(product_service), Box::new(currency_parser));
+
+        let action = router.route_message(&message).unwrap();
+        assert_eq!(
+            Some(MessageAction::Command(Command::CorrectBalance {
+                user: "Anna Muster".to_string(),
+                amount: -500
+            })),
+            action
+        );
+    }
+
+    #[test]
+    fn promote_command() {
+        let product_service = ProductServiceMock::new();
 
         let currency_parser = CurrencyParserMock::new();
 
@@ -193,41 +656,603 @@ mod tests {
                 id: "some id".to_string(),
                 name: "Test".to_string(),
             },
-            contents: "foo".to_string(),
+            contents: "/promote @anna".to_string(),
         };
 
         let router = MessageRouterImpl::new(Box::new(product_service), Box::new(currency_parser));
 
         let action = router.route_message(&message).unwrap();
-        assert_eq!(Some(MessageAction::Product(product)), action);
+        assert_eq!(
+            Some(MessageAction::Command(Command::SetRole {
+                user: "anna".to_string(),
+                role: Role::Admin
+            })),
+            action
+        );
     }
 
     #[test]
-    fn amount() {
-        let mut product_service = ProductServiceMock::new();
-        product_service
-            .expect_get_product_with_identifier(|arg| arg.partial_eq("1.20"))
+    fn topup_command() {
+        let product_service = ProductServiceMock::new();
+
+        let mut currency_parser = CurrencyParserMock::new();
+        currency_parser
+            .expect_parse_text(|arg| arg.partial_eq("20"))
             .times(1)
-            .returns(Ok(None));
+            .returns_once(Ok(2000));
+
+        let router = MessageRouterImpl::new(Box::new(product_service), Box::new(currency_parser));
+
+        let message = |contents: &str| Message {
+            sender: User {
+                id: "some id".to_string(),
+                name: "Test".to_string(),
+            },
+            contents: contents.to_string(),
+        };
+
+        assert_eq!(
+            Some(MessageAction::Command(Command::TopUp(None))),
+            router.route_message(&message("/topup")).unwrap()
+        );
+        assert_eq!(
+            Some(MessageAction::Command(Command::TopUp(Some(2000)))),
+            router.route_message(&message("/topup 20")).unwrap()
+        );
+    }
+
+    #[test]
+    fn expense_command() {
+        let product_service = ProductServiceMock::new();
 
         let mut currency_parser = CurrencyParserMock::new();
         currency_parser
-            .expect_parse_text(|arg| arg.partial_eq("1.20"))
+            .expect_parse_text(|arg| arg.partial_eq("24.90"))
             .times(1)
-            .returns(Ok(120));
+            .returns_once(Ok(2490));
 
         let message = Message {
             sender: User {
                 id: "some id".to_string(),
                 name: "Test".to_string(),
             },
-            contents: "1.20".to_string(),
+            contents: "/expense 24.90 coffee beans".to_string(),
         };
 
         let router = MessageRouterImpl::new(Box::new(product_service), Box::new(currency_parser));
 
         let action = router.route_message(&message).unwrap();
-        assert_eq!(Some(MessageAction::Amount(120)), action);
+        assert_eq!(
+            Some(MessageAction::Command(Command::RecordExpense {
+                amount: 2490,
+                description: "coffee beans".to_string()
+            })),
+            action
+        );
+    }
+
+    #[test]
+    fn set_limit_command() {
+        let product_service = ProductServiceMock::new();
+
+        let mut currency_parser = CurrencyParserMock::new();
+        currency_parser
+            .expect_parse_text(|arg| arg.partial_eq("20"))
+            .times(1)
+            .returns_once(Ok(2000));
+
+        let router = MessageRouterImpl::new(Box::new(product_service), Box::new(currency_parser));
+
+        let message = |contents: &str| Message {
+            sender: User {
+                id: "some id".to_string(),
+                name: "Test".to_string(),
+            },
+            contents: contents.to_string(),
+        };
+
+        assert_eq!(
+            Some(MessageAction::Command(Command::SetCreditLimit {
+                user: "anna".to_string(),
+                limit: Some(2000)
+            })),
+            router
+                .route_message(&message("/setlimit @anna 20"))
+                .unwrap()
+        );
+        assert_eq!(
+            Some(MessageAction::Command(Command::SetCreditLimit {
+                user: "anna".to_string(),
+                limit: None
+            })),
+            router
+                .route_message(&message("/setlimit @anna none"))
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn add_product_command() {
+        let product_service = ProductServiceMock::new();
+
+        let mut currency_parser = CurrencyParserMock::new();
+        currency_parser
+            .expect_parse_text(|arg| arg.partial_eq("1.50"))
+            .times(1)
+            .returns_once(Ok(150));
+
+        let message = Message {
+            sender: User {
+                id: "some id".to_string(),
+                name: "Test".to_string(),
+            },
+            contents: "/addproduct Tea \"Black tea\" 1.50".to_string(),
+        };
+
+        let router = MessageRouterImpl::new(Box::new(product_service), Box::new(currency_parser));
+
+        let action = router.route_message(&message).unwrap();
+        assert_eq!(
+            Some(MessageAction::Command(Command::AddProduct {
+                identifier: "tea".to_string(),
+                name: "Black tea".to_string(),
+                price: 150
+            })),
+            action
+        );
+    }
+
+    #[test]
+    fn rename_command() {
+        let product_service = ProductServiceMock::new();
+
+        let currency_parser = CurrencyParserMock::new();
+
+        let message = Message {
+            sender: User {
+                id: "some id".to_string(),
+                name: "Test".to_string(),
+            },
+            contents: "/rename coke “Coca-Cola Zero”".to_string(),
+        };
+
+        let router = MessageRouterImpl::new(Box::new(product_service), Box::new(currency_parser));
+
+        let action = router.route_message(&message).unwrap();
+        assert_eq!(
+            Some(MessageAction::Command(Command::RenameProduct {
+                product_identifier: "coke".to_string(),
+                name: "Coca-Cola Zero".to_string()
+            })),
+            action
+        );
+    }
+
+    #[test]
+    fn splits_quoted_arguments() {
+        assert_eq!(
+            vec!["/rename", "coke", "a coke", ""],
+            split_arguments(" /rename  coke \"a coke\" \"\"")
+        );
+    }
+
+    #[test]
+    fn statement_command_with_month() {
+        let product_service = ProductServiceMock::new();
+
+        let currency_parser = CurrencyParserMock::new();
+
+        let message = Message {
+            sender: User {
+                id: "some id".to_string(),
+                name: "Test".to_string(),
+            },
+            contents: "/statement 2026-09".to_string(),
+        };
+
+        let router = MessageRouterImpl::new(Box::new(product_service), Box::new(currency_parser));
+
+        let action = router.route_message(&message).unwrap();
+        assert_eq!(
+            Some(MessageAction::Command(Command::GetStatement(Some(
+                NaiveDate::from_ymd_opt(2026, 9, 1).unwrap()
+            )))),
+            action
+        );
+    }
+
+    #[test]
+    fn transfer() {
+        let product_service = ProductServiceMock::new();
+
+        let mut currency_parser = CurrencyParserMock::new();
+        currency_parser
+            .expect_parse_text(|arg| arg.partial_eq("2.50"))
+            .times(1)
+            .returns_once(Ok(250));
+
+        let message = Message {
+            sender: User {
+                id: "some id".to_string(),
+                name: "Test".to_string(),
+            },
+            contents: "/pay @Anna Muster 2.50".to_string(),
+        };
+
+        let router = MessageRouterImpl::new(Box::new(product_service), Box::new(currency_parser));
+
+        let action = router.route_message(&message).unwrap();
+        assert_eq!(
+            Some(MessageAction::Transfer {
+                recipient: "Anna Muster".to_string(),
+                amount: 250
+            }),
+            action
+        );
+    }
+
+    #[test]
+    fn transfer_to_quoted_recipient() {
+        let product_service = ProductServiceMock::new();
+
+        let mut currency_parser = CurrencyParserMock::new();
+        currency_parser
+            .expect_parse_text(|arg| arg.partial_eq("5"))
+            .times(1)
+            .returns_once(Ok(500));
+
+        let message = Message {
+            sender: User {
+                id: "some id".to_string(),
+                name: "Test".to_string(),
+            },
+            contents: "/pay \"Anna B\" 5".to_string(),
+        };
+
+        let router = MessageRouterImpl::new(Box::new(product_service), Box::new(currency_parser));
+
+        let action = router.route_message(&message).unwrap();
+        assert_eq!(
+            Some(MessageAction::Transfer {
+                recipient: "Anna B".to_string(),
+                amount: 500
+            }),
+            action
+        );
+    }
+
+    #[test]
+    fn known_product() {
+        let product = Product {
+            identifier: "foo".to_string(),
+            name: "test product".to_string(),
+            price: 60,
+            available: true,
+            stock: None,
+        };
+
+        let mut product_service = ProductServiceMock::new();
+        product_service
+            .expect_get_product_with_identifier(|arg| arg.partial_eq("foo"))
+            .times(1)
+            .returns_once(Ok(Some(product.clone())));
+
+        let currency_parser = CurrencyParserMock::new();
+
+        let message = Message {
+            sender: User {
+                id: "some id".to_string(),
+                name: "Test".to_string(),
+            },
+            contents: "/foo".to_string(),
+        };
+
+        let router = MessageRouterImpl::new(Box::new(product_service), Box::new(currency_parser));
+
+        let action = router.route_message(&message).unwrap();
+        assert_eq!(
+            Some(MessageAction::Product {
+                product,
+                quantity: 1
+            }),
+            action
+        );
+    }
+
+    #[test]
+    fn known_product_without_slash() {
+        let product = Product {
+            identifier: "foo".to_string(),
+            name: "test product".to_string(),
+            price: 60,
+            available: true,
+            stock: None,
+        };
+
+        let mut product_service = ProductServiceMock::new();
+        product_service
+            .expect_get_product_with_identifier(|arg| arg.partial_eq("foo"))
+            .times(1)
+            .returns_once(Ok(Some(product.clone())));
+
+        let currency_parser = CurrencyParserMock::new();
+
+        let message = Message {
+            sender: User {
+                id: "some id".to_string(),
+                name: "Test".to_string(),
+            },
+            contents: "foo".to_string(),
+        };
+
+        let router = MessageRouterImpl::new(Box::new(product_service), Box::new(currency_parser));
+
+        let action = router.route_message(&message).unwrap();
+        assert_eq!(
+            Some(MessageAction::Product {
+                product,
+                quantity: 1
+            }),
+            action
+        );
+    }
+
+    #[test]
+    fn known_product_with_quantity_prefix() {
+        let product = Product {
+            identifier: "foo".to_string(),
+            name: "test product".to_string(),
+            price: 60,
+            available: true,
+            stock: None,
+        };
+
+        let mut product_service = ProductServiceMock::new();
+        product_service
+            .expect_get_product_with_identifier(|arg| arg.partial_eq("foo"))
+            .times(1)
+            .returns_once(Ok(Some(product.clone())));
+
+        let currency_parser = CurrencyParserMock::new();
+
+        let message = Message {
+            sender: User {
+                id: "some id".to_string(),
+                name: "Test".to_string(),
+            },
+            contents: "3x foo".to_string(),
+        };
+
+        let router = MessageRouterImpl::new(Box::new(product_service), Box::new(currency_parser));
+
+        let action = router.route_message(&message).unwrap();
+        assert_eq!(
+            Some(MessageAction::Product {
+                product,
+                quantity: 3
+            }),
+            action
+        );
+    }
+
+    #[test]
+    fn product_identifier_containing_x() {
+        let product = Product {
+            identifier: "xl".to_string(),
+            name: "XL coffee".to_string(),
+            price: 300,
+            available: true,
+            stock: None,
+        };
+
+        // `2xl` is first read as two of `l`
+        for (contents, quantity, is_ambiguous) in &[("2xl", 2, true), ("3x xl", 3, false)] {
+            let mut product_service = ProductServiceMock::new();
+            if *is_ambiguous {
+                product_service
+                    .expect_get_product_with_identifier(|arg| arg.partial_eq("l"))
+                    .returns_once(Ok(None));
+            }
+            product_service
+                .expect_get_product_with_identifier(|arg| arg.partial_eq("xl"))
+                .times(1)
+                .returns_once(Ok(Some(product.clone())));
+
+            let currency_parser = CurrencyParserMock::new();
+
+            let message = Message {
+                sender: User {
+                    id: "some id".to_string(),
+                    name: "Test".to_string(),
+                },
+                contents: contents.to_string(),
+            };
+
+            let router =
+                MessageRouterImpl::new(Box::new(product_service), Box::new(currency_parser));
+
+            let action = router.route_message(&message).unwrap();
+            assert_eq!(
+                Some(MessageAction::Product {
+                    product: product.clone(),
+                    quantity: *quantity,
+                }),
+                action
+            );
+        }
+    }
+
+    #[test]
+    fn known_product_with_quantity_suffix() {
+        let product = Product {
+            identifier: "foo".to_string(),
+            name: "test product".to_string(),
+            price: 60,
+            available: true,
+            stock: None,
+        };
+
+        let mut product_service = ProductServiceMock::new();
+        product_service
+            .expect_get_product_with_identifier(|arg| arg.partial_eq("foo"))
+            .times(1)
+            .returns_once(Ok(Some(product.clone())));
+
+        let currency_parser = CurrencyParserMock::new();
+
+        let message = Message {
+            sender: User {
+                id: "some id".to_string(),
+                name: "Test".to_string(),
+            },
+            contents: "Foo 3".to_string(),
+        };
+
+        let router = MessageRouterImpl::new(Box::new(product_service), Box::new(currency_parser));
+
+        let action = router.route_message(&message).unwrap();
+        assert_eq!(
+            Some(MessageAction::Product {
+                product,
+                quantity: 3
+            }),
+            action
+        );
+    }
+
+    #[test]
+    fn known_product_with_quantity_multiplier() {
+        let product = Product {
+            identifier: "foo".to_string(),
+            name: "test product".to_string(),
+            price: 60,
+            available: true,
+            stock: None,
+        };
+
+        let mut product_service = ProductServiceMock::new();
+        product_service
+            .expect_get_product_with_identifier(|arg| arg.partial_eq("foo"))
+            .times(1)
+            .returns_once(Ok(Some(product.clone())));
+
+        let currency_parser = CurrencyParserMock::new();
+
+        let message = Message {
+            sender: User {
+                id: "some id".to_string(),
+                name: "Test".to_string(),
+            },
+            contents: "/foo*2".to_string(),
+        };
+
+        let router = MessageRouterImpl::new(Box::new(product_service), Box::new(currency_parser));
+
+        let action = router.route_message(&message).unwrap();
+        assert_eq!(
+            Some(MessageAction::Product {
+                product,
+                quantity: 2
+            }),
+            action
+        );
+    }
+
+    #[test]
+    fn known_product_with_invalid_quantity() {
+        let mut product_service = ProductServiceMock::new();
+        product_service
+            .expect_get_product_with_identifier(|arg| arg.partial_eq("foo"))
+            .times(1)
+            .returns_once(Ok(Some(Product {
+                identifier: "foo".to_string(),
+                name: "test product".to_string(),
+                price: 60,
+                available: true,
+                stock: None,
+            })));
+
+        let currency_parser = CurrencyParserMock::new();
+
+        let message = Message {
+            sender: User {
+                id: "some id".to_string(),
+                name: "Test".to_string(),
+            },
+            contents: "0x foo".to_string(),
+        };
+
+        let router = MessageRouterImpl::new(Box::new(product_service), Box::new(currency_parser));
+
+        router.route_message(&message).unwrap_err();
+    }
+
+    #[test]
+    fn unavailable_product() {
+        let mut product_service = ProductServiceMock::new();
+        product_service
+            .expect_get_product_with_identifier(|arg| arg.partial_eq("foo"))
+            .times(1)
+            .returns_once(Ok(Some(Product {
+                identifier: "foo".to_string(),
+                name: "test product".to_string(),
+                price: 60,
+                available: false,
+                stock: None,
+            })));
+
+        let currency_parser = CurrencyParserMock::new();
+
+        let message = Message {
+            sender: User {
+                id: "some id".to_string(),
+                name: "Test".to_string(),
+            },
+            contents: "/foo".to_string(),
+        };
+
+        let router = MessageRouterImpl::new(Box::new(product_service), Box::new(currency_parser));
+
+        assert_eq!(
+            Err(Error::Validation(
+                "test product is no longer available".to_string()
+            )),
+            router.route_message(&message)
+        );
+    }
+
+    #[test]
+    fn amount() {
+        let mut product_service = ProductServiceMock::new();
+        product_service
+            .expect_get_product_with_identifier(|arg| arg.partial_eq("1.20"))
+            .times(1)
+            .returns_once(Ok(None));
+
+        let mut currency_parser = CurrencyParserMock::new();
+        currency_parser
+            .expect_parse_text(|arg| arg.partial_eq("1.20"))
+            .times(1)
+            .returns_once(Ok(120));
+
+        let message = Message {
+            sender: User {
+                id: "some id".to_string(),
+                name: "Test".to_string(),
+            },
+            contents: "1.20".to_string(),
+        };
+
+        let router = MessageRouterImpl::new(Box::new(product_service), Box::new(currency_parser));
+
+        let action = router.route_message(&message).unwrap();
+        assert_eq!(
+            Some(MessageAction::Amount {
+                amount: 120,
+                kind: TransactionKind::Deposit
+            }),
+            action
+        );
     }
 
     #[test]
@@ -236,7 +1261,7 @@ mod tests {
         product_service
             .expect_get_product_with_identifier(|arg| arg.partial_eq("1.20"))
             .times(1)
-            .returns(Err(()));
+            .returns_once(Err(Error::Database(diesel::result::Error::NotFound)));
 
         let currency_parser = CurrencyParserMock::new();
 
@@ -1,4 +1,11 @@
-use chrono::{NaiveDate, NaiveDateTime};
+use std::io::Write;
+
+use chrono::{NaiveDate, NaiveDateTime, Utc};
+use diesel::backend::Backend;
+use diesel::deserialize::{self, FromSql};
+use diesel::serialize::{self, Output, ToSql};
+use diesel::sql_types::Text;
+use diesel::sqlite::Sqlite;
 
 use crate::schema::*;
 
@@ -13,19 +20,96 @@ pub struct Message {
 #[derive(Debug, PartialEq)]
 pub struct Response {
     pub contents: String,
+    pub attachment: Option<Attachment>,
+}
+
+/// A file sent along with a response.
+#[derive(Debug, PartialEq)]
+pub struct Attachment {
+    pub file_name: String,
+    pub data: Vec<u8>,
 }
 
 #[derive(Debug, PartialEq)]
 pub enum Command {
-    GetCurrentBalances,
+    GetCurrentBalances(BalanceQuery),
     ListAvailableItems,
+    GetTransactionHistory(u32),
+    /// Statement for the month starting at the given day, the current month if there is none.
+    GetStatement(Option<NaiveDate>),
+    Undo,
+    /// QR-bill for the given amount, or for the debt of the sender if there is none.
+    TopUp(Option<Rappen>),
+    RecordExpense {
+        amount: Rappen,
+        description: String,
+    },
+    GetCashPosition,
+    /// All prices a product had, by product identifier.
+    GetPriceHistory(String),
+    Restock {
+        product_identifier: String,
+        quantity: u32,
+    },
+    CorrectBalance {
+        user: String,
+        amount: Rappen,
+    },
+    SetRole {
+        user: String,
+        role: Role,
+    },
+    /// `None` removes the personal limit, so the global one applies again.
+    SetCreditLimit {
+        user: String,
+        limit: Option<Rappen>,
+    },
+    AddProduct {
+        identifier: String,
+        name: String,
+        price: Rappen,
+    },
+    SetPrice {
+        product_identifier: String,
+        price: Rappen,
+    },
+    RenameProduct {
+        product_identifier: String,
+        name: String,
+    },
+}
+
+impl Command {
+    /// Whether only admins are allowed to use the command.
+    pub fn requires_admin(&self) -> bool {
+        matches!(
+            self,
+            Command::Restock { .. }
+                | Command::CorrectBalance { .. }
+                | Command::SetRole { .. }
+                | Command::SetCreditLimit { .. }
+                | Command::AddProduct { .. }
+                | Command::SetPrice { .. }
+                | Command::RenameProduct { .. }
+        )
+    }
 }
 
 #[derive(Debug, PartialEq)]
 pub enum MessageAction {
-    Amount(Rappen),
+    Amount {
+        amount: Rappen,
+        kind: TransactionKind,
+    },
     Command(Command),
-    Product(Product),
+    Product {
+        product: Product,
+        quantity: u32,
+    },
+    Transfer {
+        recipient: String,
+        amount: Rappen,
+    },
 }
 
 #[derive(Queryable, Insertable, Identifiable, Clone, Debug)]
@@ -34,6 +118,10 @@ pub struct Product {
     pub identifier: String,
     pub name: String,
     pub price: Rappen,
+    /// Unavailable products are no longer sold but are kept for past transactions.
+    pub available: bool,
+    /// Number of units in stock, `None` if the stock is not tracked.
+    pub stock: Option<i32>,
 }
 
 impl PartialEq for Product {
@@ -42,6 +130,60 @@ impl PartialEq for Product {
     }
 }
 
+/// The price of a product during a period of time. The current price has no end.
+#[derive(Queryable, Identifiable, Clone, Debug, PartialEq)]
+pub struct ProductPrice {
+    pub id: i32,
+    pub product_identifier: String,
+    pub price: Rappen,
+    pub valid_from: NaiveDateTime,
+    pub valid_until: Option<NaiveDateTime>,
+}
+
+#[derive(Insertable, Debug)]
+#[table_name = "product_prices"]
+pub(crate) struct NewProductPrice<'a> {
+    pub(crate) product_identifier: &'a str,
+    pub(crate) price: Rappen,
+    pub(crate) valid_from: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug)]
+#[table_name = "expenses"]
+pub(crate) struct NewExpense<'a> {
+    pub(crate) transaction_id: i32,
+    pub(crate) amount: Rappen,
+    pub(crate) description: &'a str,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BalanceOrder {
+    Name,
+    /// Lowest balance first, i.e. whoever owes the most.
+    Amount,
+}
+
+/// Which balances to list and in which order.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BalanceQuery {
+    pub order: BalanceOrder,
+    pub hide_zero_balances: bool,
+    /// Only include users with a transaction during the last days.
+    pub active_within_days: Option<u32>,
+    pub user_id: Option<String>,
+}
+
+impl Default for BalanceQuery {
+    fn default() -> Self {
+        Self {
+            order: BalanceOrder::Name,
+            hide_zero_balances: false,
+            active_within_days: None,
+            user_id: None,
+        }
+    }
+}
+
 #[derive(Queryable, Clone, Debug)]
 pub struct Balance {
     pub user_id: String,
@@ -61,10 +203,189 @@ impl PartialEq for User {
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Role {
+    Member,
+    Admin,
+}
+
+impl Role {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Role::Member => "member",
+            Role::Admin => "admin",
+        }
+    }
+
+    pub fn parse(text: &str) -> Option<Self> {
+        match text {
+            "member" => Some(Role::Member),
+            "admin" => Some(Role::Admin),
+            _ => None,
+        }
+    }
+}
+
+#[derive(AsExpression, FromSqlRow, Clone, Copy, Debug, PartialEq)]
+#[sql_type = "Text"]
+pub enum TransactionKind {
+    Purchase,
+    /// Money paid into the kitty.
+    Deposit,
+    /// A balance adjustment made by an admin.
+    Correction,
+    /// Money paid back for supplies bought for everyone.
+    ExpenseReimbursement,
+    Transfer,
+}
+
+impl TransactionKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TransactionKind::Purchase => "purchase",
+            TransactionKind::Deposit => "deposit",
+            TransactionKind::Correction => "correction",
+            TransactionKind::ExpenseReimbursement => "expense_reimbursement",
+            TransactionKind::Transfer => "transfer",
+        }
+    }
+
+    pub fn parse(text: &str) -> Option<Self> {
+        match text {
+            "purchase" => Some(TransactionKind::Purchase),
+            "deposit" => Some(TransactionKind::Deposit),
+            "correction" => Some(TransactionKind::Correction),
+            "expense_reimbursement" => Some(TransactionKind::ExpenseReimbursement),
+            "transfer" => Some(TransactionKind::Transfer),
+            _ => None,
+        }
+    }
+
+    /// The kind as shown to users, e.g. `expense reimbursement`.
+    pub fn label(self) -> &'static str {
+        match self {
+            TransactionKind::ExpenseReimbursement => "expense reimbursement",
+            kind => kind.as_str(),
+        }
+    }
+}
+
+impl ToSql<Text, Sqlite> for TransactionKind {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Sqlite>) -> serialize::Result {
+        ToSql::<Text, Sqlite>::to_sql(self.as_str(), out)
+    }
+}
+
+impl FromSql<Text, Sqlite> for TransactionKind {
+    fn from_sql(bytes: Option<&<Sqlite as Backend>::RawValue>) -> deserialize::Result<Self> {
+        let text = <String as FromSql<Text, Sqlite>>::from_sql(bytes)?;
+        TransactionKind::parse(&text)
+            .ok_or_else(|| format!("Unknown transaction kind \"{}\"", text).into())
+    }
+}
+
+#[derive(Queryable, Identifiable, Clone, Debug, PartialEq)]
+pub struct Transaction {
+    pub id: i32,
+    pub amount: Rappen,
+    pub timestamp: NaiveDateTime,
+    pub user: String,
+    /// Name of the product at the time of the purchase.
+    pub product_name: Option<String>,
+    pub counterparty: Option<String>,
+    pub product_identifier: Option<String>,
+    /// Price of a single unit at the time of the purchase.
+    pub product_price: Option<Rappen>,
+    pub quantity: Option<i32>,
+    /// Identifies transactions imported from an external ledger.
+    pub import_hash: Option<String>,
+    pub kind: TransactionKind,
+}
+
+impl Transaction {
+    /// A short description such as `3× a coke`, `transfer` or `deposit`.
+    pub fn description(&self) -> String {
+        match (self.kind, &self.product_name) {
+            (TransactionKind::Purchase, Some(product_name)) => match self.quantity {
+                Some(quantity) if quantity > 1 => format!("{}× {}", quantity, product_name),
+                _ => product_name.clone(),
+            },
+            (kind, _) => kind.label().to_string(),
+        }
+    }
+}
+
+/// The money in the kitty compared to what it owes its users.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CashPosition {
+    /// Sum of all deposits. Reimbursed expenses count as deposits of the buyer.
+    pub deposits: Rappen,
+    /// Sum of all expenses, paid from the kitty's cash.
+    pub expenses: Rappen,
+    /// Sum of all user balances.
+    pub balances: Rappen,
+}
+
+impl CashPosition {
+    pub fn cash(&self) -> Rappen {
+        self.deposits - self.expenses
+    }
+
+    /// What is left once every balance is settled.
+    pub fn surplus(&self) -> Rappen {
+        self.cash() - self.balances
+    }
+}
+
+/// The transactions of a user during one month.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Statement {
+    pub user: User,
+    /// First day of the month.
+    pub month: NaiveDate,
+    pub opening_balance: Rappen,
+    /// Oldest first.
+    pub transactions: Vec<Transaction>,
+    /// Sum of all incoming amounts.
+    pub deposits: Rappen,
+    /// Sum of all outgoing amounts, as a positive number.
+    pub consumption: Rappen,
+    pub closing_balance: Rappen,
+}
+
 #[derive(Insertable, Debug)]
-pub(crate) struct Transaction {
+#[table_name = "transactions"]
+pub(crate) struct NewTransaction {
     pub(crate) amount: i32,
     pub(crate) timestamp: NaiveDateTime,
     pub(crate) user: String,
     pub(crate) product_name: Option<String>,
+    pub(crate) counterparty: Option<String>,
+    pub(crate) product_identifier: Option<String>,
+    pub(crate) product_price: Option<Rappen>,
+    pub(crate) quantity: Option<i32>,
+    pub(crate) import_hash: Option<String>,
+    pub(crate) kind: TransactionKind,
+}
+
+impl NewTransaction {
+    /// A deposit, or a purchase if the amount is negative.
+    pub(crate) fn new(amount: Rappen, user: &User) -> Self {
+        Self {
+            amount,
+            timestamp: Utc::now().naive_utc(),
+            user: user.id.clone(),
+            product_name: None,
+            counterparty: None,
+            product_identifier: None,
+            product_price: None,
+            quantity: None,
+            import_hash: None,
+            kind: if amount < 0 {
+                TransactionKind::Purchase
+            } else {
+                TransactionKind::Deposit
+            },
+        }
+    }
 }
@@ -0,0 +1,460 @@
+use std::io::Cursor;
+
+use image::{DynamicImage, ImageBuffer, ImageFormat, Luma};
+use qrcode::render::svg;
+use qrcode::{EcLevel, QrCode};
+
+use crate::error::Error;
+use crate::models::Rappen;
+
+/// Size of a single QR code module in the rendered images.
+const MODULE_SIZE: u32 = 8;
+
+/// Width of the quiet zone around the QR code, in modules.
+const QUIET_ZONE: u32 = 4;
+
+/// The account that receives the payments.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Creditor {
+    /// A Swiss or Liechtenstein IBAN or QR-IBAN, without spaces.
+    pub iban: String,
+    pub name: String,
+    pub street: Option<String>,
+    pub building_number: Option<String>,
+    pub postal_code: String,
+    pub town: String,
+    /// Two letter ISO country code.
+    pub country: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Reference {
+    /// 27 digit QR reference, required for QR-IBANs.
+    Qr(String),
+    /// ISO 11649 creditor reference, used with regular IBANs.
+    Creditor(String),
+}
+
+impl Reference {
+    /// Encodes a user id as a reference that fits the creditor's account.
+    pub fn for_user(iban: &str, user_id: &str) -> Result<Self, Error> {
+        if is_qr_iban(iban) {
+            if user_id.is_empty()
+                || user_id.len() > 26
+                || !user_id.chars().all(|character| character.is_ascii_digit())
+            {
+                return Err(Error::Validation(format!(
+                    "User id \"{}\" can't be encoded as a QR reference",
+                    user_id
+                )));
+            }
+
+            let reference = format!("{:0>26}", user_id);
+            let check_digit = qr_reference_check_digit(&reference);
+            Ok(Reference::Qr(format!("{}{}", reference, check_digit)))
+        } else {
+            let user_id = user_id.to_uppercase();
+            if user_id.is_empty()
+                || user_id.len() > 21
+                || !user_id
+                    .chars()
+                    .all(|character| character.is_ascii_alphanumeric())
+            {
+                return Err(Error::Validation(format!(
+                    "User id \"{}\" can't be encoded as a creditor reference",
+                    user_id
+                )));
+            }
+
+            let check_digits = 98 - mod_97(&format!("{}RF00", user_id));
+            Ok(Reference::Creditor(format!(
+                "RF{:02}{}",
+                check_digits, user_id
+            )))
+        }
+    }
+
+    /// Reads a reference from a payment, ignoring whitespace.
+    /// Returns `None` if it is neither a valid QR nor creditor reference.
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text
+            .chars()
+            .filter(|character| !character.is_whitespace())
+            .collect::<String>()
+            .to_uppercase();
+
+        if text.len() == 27 && text.chars().all(|character| character.is_ascii_digit()) {
+            let (reference, check_digit) = text.split_at(26);
+            return if qr_reference_check_digit(reference).to_string() == check_digit {
+                Some(Reference::Qr(text))
+            } else {
+                None
+            };
+        }
+
+        if text.starts_with("RF")
+            && (5..=25).contains(&text.len())
+            && text
+                .chars()
+                .all(|character| character.is_ascii_alphanumeric())
+            && mod_97(&format!("{}{}", &text[4..], &text[..4])) == 1
+        {
+            return Some(Reference::Creditor(text));
+        }
+
+        None
+    }
+
+    /// The user id encoded by [`Reference::for_user`]. Ids in creditor references are uppercase.
+    pub fn user_id(&self) -> String {
+        match self {
+            Reference::Qr(reference) => {
+                let user_id = reference[..26].trim_start_matches('0');
+                if user_id.is_empty() {
+                    "0".to_string()
+                } else {
+                    user_id.to_string()
+                }
+            }
+            Reference::Creditor(reference) => reference[4..].to_string(),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Reference::Qr(reference) | Reference::Creditor(reference) => reference,
+        }
+    }
+
+    fn type_code(&self) -> &'static str {
+        match self {
+            Reference::Qr(_) => "QRR",
+            Reference::Creditor(_) => "SCOR",
+        }
+    }
+}
+
+/// The data of a Swiss QR-bill. Without an amount, the payer fills it in.
+#[derive(Clone, Debug, PartialEq)]
+pub struct QrBill {
+    pub creditor: Creditor,
+    pub amount: Option<Rappen>,
+    pub reference: Reference,
+    pub message: Option<String>,
+}
+
+impl QrBill {
+    /// The contents of the QR code in the SPC 0200 format.
+    pub fn payload(&self) -> Result<String, Error> {
+        let iban = normalize_iban(&self.creditor.iban)?;
+
+        let amount = match self.amount {
+            Some(amount) if amount <= 0 => {
+                return Err(Error::Validation(
+                    "The amount of a QR-bill must be positive".to_string(),
+                ))
+            }
+            Some(amount) => format!("{}.{:02}", amount / 100, amount % 100),
+            None => String::new(),
+        };
+
+        let creditor = &self.creditor;
+        let mut lines = vec![
+            "SPC",
+            "0200",
+            "1",
+            &iban,
+            "S",
+            &creditor.name,
+            creditor.street.as_deref().unwrap_or_default(),
+            creditor.building_number.as_deref().unwrap_or_default(),
+            &creditor.postal_code,
+            &creditor.town,
+            &creditor.country,
+        ];
+        // Ultimate creditor, reserved for future use
+        lines.extend(&["", "", "", "", "", "", ""]);
+        lines.extend(&[amount.as_str(), "CHF"]);
+        // Ultimate debtor, left empty so the payer's own address is used
+        lines.extend(&["", "", "", "", "", "", ""]);
+        lines.extend(&[
+            self.reference.type_code(),
+            self.reference.as_str(),
+            self.message.as_deref().unwrap_or_default(),
+            "EPD",
+        ]);
+
+        Ok(lines.join("\n"))
+    }
+
+    pub fn render_svg(&self) -> Result<String, Error> {
+        let code = self.qr_code()?;
+        let svg = code
+            .render::<svg::Color>()
+            .quiet_zone(true)
+            .module_dimensions(MODULE_SIZE, MODULE_SIZE)
+            .build();
+
+        let cross = swiss_cross(code.width() as u32)
+            .iter()
+            .map(|(x, y, size, dark)| {
+                format!(
+                    r#"<rect x="{}" y="{}" width="{}" height="{}" fill="{}"/>"#,
+                    x,
+                    y,
+                    size.0,
+                    size.1,
+                    if *dark { "#000" } else { "#fff" }
+                )
+            })
+            .collect::<String>();
+
+        Ok(svg.replacen("</svg>", &format!("{}</svg>", cross), 1))
+    }
+
+    pub fn render_png(&self) -> Result<Vec<u8>, Error> {
+        let code = self.qr_code()?;
+        let mut image = code
+            .render::<Luma<u8>>()
+            .quiet_zone(true)
+            .module_dimensions(MODULE_SIZE, MODULE_SIZE)
+            .build();
+
+        for (x, y, (width, height), dark) in swiss_cross(code.width() as u32) {
+            let color = Luma([if dark { 0 } else { 255 }]);
+            for pixel_x in x..x + width {
+                for pixel_y in y..y + height {
+                    image.put_pixel(pixel_x, pixel_y, color);
+                }
+            }
+        }
+
+        encode_png(image)
+    }
+
+    fn qr_code(&self) -> Result<QrCode, Error> {
+        // The standard requires error correction level M
+        QrCode::with_error_correction_level(self.payload()?, EcLevel::M)
+            .map_err(|error| Error::Validation(error.to_string()))
+    }
+}
+
+/// Removes spaces and checks the country and check digits.
+pub fn normalize_iban(iban: &str) -> Result<String, Error> {
+    let iban = iban
+        .chars()
+        .filter(|character| !character.is_whitespace())
+        .collect::<String>()
+        .to_uppercase();
+
+    let is_valid = (iban.starts_with("CH") || iban.starts_with("LI"))
+        && iban.len() == 21
+        && iban
+            .chars()
+            .all(|character| character.is_ascii_alphanumeric())
+        && mod_97(&format!("{}{}", &iban[4..], &iban[..4])) == 1;
+
+    if is_valid {
+        Ok(iban)
+    } else {
+        Err(Error::Validation(format!("Invalid IBAN \"{}\"", iban)))
+    }
+}
+
+/// Formats an IBAN in groups of four characters.
+pub fn format_iban(iban: &str) -> String {
+    iban.chars()
+        .collect::<Vec<_>>()
+        .chunks(4)
+        .map(|chunk| chunk.iter().collect::<String>())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// QR-IBANs have an institution id between 30000 and 31999.
+fn is_qr_iban(iban: &str) -> bool {
+    normalize_iban(iban)
+        .ok()
+        .and_then(|iban| iban[4..9].parse::<u32>().ok())
+        .is_some_and(|institution_id| (30000..=31999).contains(&institution_id))
+}
+
+/// The recursive modulo 10 check digit of QR references.
+fn qr_reference_check_digit(reference: &str) -> u32 {
+    const TABLE: [u32; 10] = [0, 9, 4, 6, 8, 2, 7, 1, 3, 5];
+
+    let carry = reference
+        .chars()
+        .filter_map(|character| character.to_digit(10))
+        .fold(0, |carry, digit| TABLE[((carry + digit) % 10) as usize]);
+
+    (10 - carry) % 10
+}
+
+/// ISO 7064 modulo 97 over alphanumeric text, with letters counting as 10 to 35.
+fn mod_97(text: &str) -> u32 {
+    text.chars()
+        .filter_map(|character| character.to_digit(36))
+        .fold(0, |remainder, value| {
+            if value < 10 {
+                (remainder * 10 + value) % 97
+            } else {
+                (remainder * 100 + value) % 97
+            }
+        })
+}
+
+/// The rectangles of the Swiss cross in the center of the code, as
+/// `(x, y, (width, height), dark)` in pixels, including the quiet zone.
+fn swiss_cross(modules: u32) -> Vec<(u32, u32, (u32, u32), bool)> {
+    let code_size = modules * MODULE_SIZE;
+    let center = (modules + 2 * QUIET_ZONE) * MODULE_SIZE / 2;
+
+    // The cross measures 7mm on a 46mm code
+    let square = |size: u32, dark: bool| {
+        let size = code_size * size / 460;
+        (center - size / 2, center - size / 2, (size, size), dark)
+    };
+    let bar = |length: u32, thickness: u32, vertical: bool| {
+        let length = code_size * length / 460;
+        let thickness = code_size * thickness / 460;
+        if vertical {
+            (
+                center - thickness / 2,
+                center - length / 2,
+                (thickness, length),
+                false,
+            )
+        } else {
+            (
+                center - length / 2,
+                center - thickness / 2,
+                (length, thickness),
+                false,
+            )
+        }
+    };
+
+    vec![
+        square(70, false),
+        square(60, true),
+        bar(39, 12, false),
+        bar(39, 12, true),
+    ]
+}
+
+fn encode_png(image: ImageBuffer<Luma<u8>, Vec<u8>>) -> Result<Vec<u8>, Error> {
+    let mut png = Vec::new();
+    DynamicImage::ImageLuma8(image)
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .map_err(|error| Error::Io(error.to_string()))?;
+
+    Ok(png)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn creditor(iban: &str) -> Creditor {
+        Creditor {
+            iban: iban.to_string(),
+            name: "Kafi Kässeli".to_string(),
+            street: Some("Bahnhofstrasse".to_string()),
+            building_number: Some("1".to_string()),
+            postal_code: "8001".to_string(),
+            town: "Zürich".to_string(),
+            country: "CH".to_string(),
+        }
+    }
+
+    #[test]
+    fn validates_iban() {
+        assert_eq!(
+            Ok("CH4431999123000889012".to_string()),
+            normalize_iban("ch44 3199 9123 0008 8901 2")
+        );
+        assert!(normalize_iban("CH4431999123000889013").is_err());
+        assert!(normalize_iban("DE89370400440532013000").is_err());
+        assert_eq!(
+            "CH44 3199 9123 0008 8901 2",
+            format_iban("CH4431999123000889012")
+        );
+    }
+
+    #[test]
+    fn encodes_user_id_as_qr_reference() {
+        let reference = Reference::for_user("CH4431999123000889012", "1234").unwrap();
+
+        assert_eq!(
+            Reference::Qr("000000000000000000000012347".to_string()),
+            reference
+        );
+        assert_eq!(
+            Some(reference.clone()),
+            Reference::parse(reference.as_str())
+        );
+        assert_eq!("1234", reference.user_id());
+        assert_eq!(None, Reference::parse("000000000000000000000012348"));
+        assert!(Reference::for_user("CH4431999123000889012", "cli").is_err());
+    }
+
+    #[test]
+    fn encodes_user_id_as_creditor_reference() {
+        let reference = Reference::for_user("CH9300762011623852957", "cli").unwrap();
+
+        assert_eq!(Reference::Creditor("RF42CLI".to_string()), reference);
+        assert_eq!(Some(reference.clone()), Reference::parse("rf42 cli"));
+        assert_eq!("CLI", reference.user_id());
+        assert_eq!(None, Reference::parse("RF43CLI"));
+    }
+
+    #[test]
+    fn creates_payload() {
+        let qr_bill = QrBill {
+            creditor: creditor("CH44 3199 9123 0008 8901 2"),
+            amount: Some(1250),
+            reference: Reference::for_user("CH4431999123000889012", "1234").unwrap(),
+            message: Some("Top-up for Anna".to_string()),
+        };
+
+        assert_eq!(
+            "SPC\n0200\n1\nCH4431999123000889012\n\
+             S\nKafi Kässeli\nBahnhofstrasse\n1\n8001\nZürich\nCH\n\
+             \n\n\n\n\n\n\n\
+             12.50\nCHF\n\
+             \n\n\n\n\n\n\n\
+             QRR\n000000000000000000000012347\nTop-up for Anna\nEPD",
+            qr_bill.payload().unwrap()
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_amounts() {
+        let qr_bill = QrBill {
+            creditor: creditor("CH9300762011623852957"),
+            amount: Some(0),
+            reference: Reference::Creditor("RF42CLI".to_string()),
+            message: None,
+        };
+
+        assert!(qr_bill.payload().is_err());
+    }
+
+    #[test]
+    fn renders_images() {
+        let qr_bill = QrBill {
+            creditor: creditor("CH9300762011623852957"),
+            amount: None,
+            reference: Reference::Creditor("RF42CLI".to_string()),
+            message: None,
+        };
+
+        let svg = qr_bill.render_svg().unwrap();
+        assert!(svg.starts_with("<?xml"));
+        assert!(svg.ends_with(r##"fill="#fff"/></svg>"##));
+
+        let png = qr_bill.render_png().unwrap();
+        assert_eq!(b"\x89PNG", &png[..4]);
+    }
+}
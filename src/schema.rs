@@ -3,6 +3,34 @@ table! {
         identifier -> Text,
         name -> Text,
         price -> Integer,
+        available -> Bool,
+        stock -> Nullable<Integer>,
+    }
+}
+
+table! {
+    expenses {
+        id -> Integer,
+        transaction_id -> Integer,
+        amount -> Integer,
+        description -> Text,
+    }
+}
+
+table! {
+    payer_mappings (payer) {
+        payer -> Text,
+        user_id -> Text,
+    }
+}
+
+table! {
+    product_prices {
+        id -> Integer,
+        product_identifier -> Text,
+        price -> Integer,
+        valid_from -> Timestamp,
+        valid_until -> Nullable<Timestamp>,
     }
 }
 
@@ -12,7 +40,13 @@ table! {
         amount -> Integer,
         timestamp -> Timestamp,
         user -> Text,
-        product_name -> Text,
+        product_name -> Nullable<Text>,
+        counterparty -> Nullable<Text>,
+        product_identifier -> Nullable<Text>,
+        product_price -> Nullable<Integer>,
+        quantity -> Nullable<Integer>,
+        import_hash -> Nullable<Text>,
+        kind -> Text,
     }
 }
 
@@ -20,6 +54,8 @@ table! {
     users {
         id -> Text,
         name -> Text,
+        role -> Text,
+        credit_limit -> Nullable<Integer>,
     }
 }
 
@@ -31,6 +67,18 @@ table! {
     }
 }
 
+joinable!(expenses -> transactions (transaction_id));
 joinable!(transactions -> users (user));
+joinable!(transactions -> products (product_identifier));
+joinable!(product_prices -> products (product_identifier));
+joinable!(payer_mappings -> users (user_id));
 
-allow_tables_to_appear_in_same_query!(products, transactions, users,);
+allow_tables_to_appear_in_same_query!(
+    balances,
+    expenses,
+    payer_mappings,
+    product_prices,
+    products,
+    transactions,
+    users,
+);
@@ -1,3 +1,5 @@
+use chrono::{Duration, Utc};
+use diesel::expression::functions::aggregate_folding::sum;
 use diesel::SqliteConnection;
 use diesel::{ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl};
 #[cfg(test)]
@@ -5,12 +7,25 @@ use mockiato::mockable;
 
 use balances::dsl::balances as balances_dsl;
 
-use crate::models::Balance;
-use crate::schema::balances;
+use crate::error::Error;
+use crate::models::{
+    Balance, BalanceOrder, BalanceQuery, CashPosition, Rappen, TransactionKind, User,
+};
+use crate::schema::{balances, expenses, transactions};
+
+sql_function!(fn lower(x: diesel::sql_types::Text) -> diesel::sql_types::Text);
 
 #[cfg_attr(test, mockable)]
 pub trait BalanceService {
-    fn get_balances(&self) -> Result<Vec<Balance>, ()>;
+    /// All balances, ordered by name.
+    fn get_balances(&self) -> Result<Vec<Balance>, Error>;
+
+    fn query_balances(&self, query: &BalanceQuery) -> Result<Vec<Balance>, Error>;
+
+    /// The balance of a single user, zero for unknown users.
+    fn get_balance(&self, user: &User) -> Result<Rappen, Error>;
+
+    fn get_cash_position(&self) -> Result<CashPosition, Error>;
 }
 
 pub struct BalanceServiceImpl<'a> {
@@ -26,14 +41,256 @@ impl<'a> BalanceServiceImpl<'a> {
 }
 
 impl BalanceService for BalanceServiceImpl<'_> {
-    fn get_balances(&self) -> Result<Vec<Balance>, ()> {
-        balances_dsl
+    fn get_balances(&self) -> Result<Vec<Balance>, Error> {
+        self.query_balances(&BalanceQuery::default())
+    }
+
+    fn query_balances(&self, query: &BalanceQuery) -> Result<Vec<Balance>, Error> {
+        let mut balances_query = balances_dsl.into_boxed();
+
+        balances_query = match query.order {
+            BalanceOrder::Name => balances_query.order((lower(balances::name), balances::user_id)),
+            BalanceOrder::Amount => balances_query.order((balances::amount, lower(balances::name))),
+        };
+
+        if query.hide_zero_balances {
+            balances_query = balances_query.filter(balances::amount.ne(0));
+        }
+
+        if let Some(days) = query.active_within_days {
+            let since = Utc::now().naive_utc() - Duration::days(days.into());
+            balances_query = balances_query.filter(
+                balances::user_id.eq_any(
+                    transactions::table
+                        .filter(transactions::timestamp.ge(since))
+                        .select(transactions::user),
+                ),
+            );
+        }
+
+        if let Some(user_id) = &query.user_id {
+            balances_query = balances_query.filter(balances::user_id.eq(user_id));
+        }
+
+        balances_query
             .load::<Balance>(self.database_connection)
-            .map_err(|_| ())
+            .map_err(Error::Database)
+    }
+
+    fn get_balance(&self, user: &User) -> Result<Rappen, Error> {
+        balances_dsl
+            .find(&user.id)
+            .select(balances::amount)
+            .first::<Rappen>(self.database_connection)
+            .optional()
+            .map(|amount| amount.unwrap_or(0))
+            .map_err(Error::Database)
+    }
+
+    fn get_cash_position(&self) -> Result<CashPosition, Error> {
+        let deposits = transactions::table
+            .filter(transactions::kind.eq_any(vec![
+                TransactionKind::Deposit,
+                TransactionKind::ExpenseReimbursement,
+            ]))
+            .select(sum(transactions::amount))
+            .first::<Option<i64>>(self.database_connection)
+            .map_err(Error::Database)?;
+
+        let expenses = expenses::table
+            .select(sum(expenses::amount))
+            .first::<Option<i64>>(self.database_connection)
+            .map_err(Error::Database)?;
+
+        let balances = transactions::table
+            .select(sum(transactions::amount))
+            .first::<Option<i64>>(self.database_connection)
+            .map_err(Error::Database)?;
+
+        Ok(CashPosition {
+            deposits: deposits.unwrap_or(0) as Rappen,
+            expenses: expenses.unwrap_or(0) as Rappen,
+            balances: balances.unwrap_or(0) as Rappen,
+        })
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::models::{NewExpense, NewTransaction};
+    use crate::schema::users;
+    use crate::test_utils::*;
+
     use super::*;
+
+    fn insert_user(database_connection: &SqliteConnection, id: &str, name: &str) -> User {
+        let user = User {
+            id: id.to_string(),
+            name: name.to_string(),
+        };
+
+        diesel::insert_into(users::table)
+            .values(&user)
+            .execute(database_connection)
+            .unwrap();
+
+        user
+    }
+
+    #[test]
+    fn get_balances_with_empty_database() {
+        let database_connection = setup_in_memory_database();
+
+        let balance_service = BalanceServiceImpl::new(&database_connection);
+
+        assert!(balance_service.get_balances().unwrap().is_empty());
+    }
+
+    #[test]
+    fn get_balances_sums_transactions() {
+        let database_connection = setup_in_memory_database();
+        let anna = insert_user(&database_connection, "1", "Anna");
+        let ben = insert_user(&database_connection, "2", "Ben");
+
+        diesel::insert_into(transactions::table)
+            .values(&vec![
+                NewTransaction::new(1000, &anna),
+                NewTransaction::new(-420, &anna),
+                NewTransaction::new(-150, &ben),
+            ])
+            .execute(&database_connection)
+            .unwrap();
+
+        let balance_service = BalanceServiceImpl::new(&database_connection);
+
+        let mut balances = balance_service.get_balances().unwrap();
+        balances.sort_by(|a, b| a.user_id.cmp(&b.user_id));
+        assert_eq!(
+            vec![("Anna".to_string(), 580), ("Ben".to_string(), -150)],
+            balances
+                .into_iter()
+                .map(|balance| (balance.name, balance.amount))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn query_balances() {
+        let database_connection = setup_in_memory_database();
+        let anna = insert_user(&database_connection, "1", "anna");
+        let ben = insert_user(&database_connection, "2", "Ben");
+        insert_user(&database_connection, "3", "Carla");
+
+        diesel::insert_into(transactions::table)
+            .values(&vec![
+                NewTransaction::new(-420, &anna),
+                NewTransaction {
+                    timestamp: Utc::now().naive_utc() - Duration::days(60),
+                    ..NewTransaction::new(-1000, &ben)
+                },
+            ])
+            .execute(&database_connection)
+            .unwrap();
+
+        let balance_service = BalanceServiceImpl::new(&database_connection);
+        let names = |query: BalanceQuery| {
+            balance_service
+                .query_balances(&query)
+                .unwrap()
+                .into_iter()
+                .map(|balance| balance.name)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(vec!["anna", "Ben", "Carla"], names(BalanceQuery::default()));
+        assert_eq!(
+            vec!["Ben", "anna", "Carla"],
+            names(BalanceQuery {
+                order: BalanceOrder::Amount,
+                ..BalanceQuery::default()
+            })
+        );
+        assert_eq!(
+            vec!["anna", "Ben"],
+            names(BalanceQuery {
+                hide_zero_balances: true,
+                ..BalanceQuery::default()
+            })
+        );
+        assert_eq!(
+            vec!["anna"],
+            names(BalanceQuery {
+                active_within_days: Some(30),
+                ..BalanceQuery::default()
+            })
+        );
+        assert_eq!(
+            vec!["Ben"],
+            names(BalanceQuery {
+                user_id: Some("2".to_string()),
+                ..BalanceQuery::default()
+            })
+        );
+    }
+
+    #[test]
+    fn get_balances_includes_users_without_transactions() {
+        let database_connection = setup_in_memory_database();
+        insert_user(&database_connection, "1", "Anna");
+
+        let balance_service = BalanceServiceImpl::new(&database_connection);
+
+        let balances = balance_service.get_balances().unwrap();
+        assert_eq!(1, balances.len());
+        assert_eq!(
+            Ok(0),
+            balance_service.get_balance(&User {
+                id: "1".to_string(),
+                name: "Anna".to_string(),
+            })
+        );
+        assert_eq!("1", balances[0].user_id);
+        assert_eq!(0, balances[0].amount);
+    }
+
+    #[test]
+    fn get_cash_position() {
+        let database_connection = setup_in_memory_database();
+        let anna = insert_user(&database_connection, "1", "Anna");
+        let ben = insert_user(&database_connection, "2", "Ben");
+
+        diesel::insert_into(transactions::table)
+            .values(&vec![
+                NewTransaction::new(5000, &anna),
+                NewTransaction::new(-420, &anna),
+                NewTransaction {
+                    kind: TransactionKind::ExpenseReimbursement,
+                    ..NewTransaction::new(2490, &ben)
+                },
+            ])
+            .execute(&database_connection)
+            .unwrap();
+        diesel::insert_into(expenses::table)
+            .values(&NewExpense {
+                transaction_id: 3,
+                amount: 2490,
+                description: "coffee beans",
+            })
+            .execute(&database_connection)
+            .unwrap();
+
+        let balance_service = BalanceServiceImpl::new(&database_connection);
+        let cash_position = balance_service.get_cash_position().unwrap();
+
+        assert_eq!(
+            CashPosition {
+                deposits: 7490,
+                expenses: 2490,
+                balances: 7070,
+            },
+            cash_position
+        );
+        assert_eq!(5000, cash_position.cash());
+        assert_eq!(-2070, cash_position.surplus());
+    }
 }
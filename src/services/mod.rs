@@ -1,4 +1,5 @@
 pub mod balance_service;
 pub mod product_service;
+pub mod statement_service;
 pub mod transaction_service;
 pub mod user_service;
@@ -1,17 +1,95 @@
-use diesel::SqliteConnection;
+use chrono::{NaiveDateTime, Utc};
+use diesel::{BoolExpressionMethods, Connection, SqliteConnection};
 use diesel::{ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl};
 #[cfg(test)]
 use mockiato::mockable;
 
 use products::dsl::products as products_dsl;
 
-use crate::models::Product;
-use crate::schema::products;
+use crate::error::Error;
+use crate::models::{NewProductPrice, Product, ProductPrice, Rappen};
+use crate::schema::{product_prices, products};
 
 #[cfg_attr(test, mockable)]
 pub trait ProductService {
-    fn get_available_products(&self) -> Result<Vec<Product>, ()>;
-    fn get_product_with_identifier(&self, identifier: &str) -> Result<Option<Product>, ()>;
+    fn get_available_products(&self) -> Result<Vec<Product>, Error>;
+    fn get_product_with_identifier(&self, identifier: &str) -> Result<Option<Product>, Error>;
+
+    /// Adds units to the stock of a product and starts tracking its stock if it wasn't yet.
+    fn restock_product(&self, identifier: &str, quantity: u32) -> Result<Product, Error>;
+
+    fn add_product(&self, product: &Product) -> Result<(), Error>;
+
+    fn set_price(&self, identifier: &str, price: Rappen) -> Result<Product, Error>;
+
+    fn rename_product(&self, identifier: &str, name: &str) -> Result<Product, Error>;
+
+    /// The price that was valid at the given time, if the product already existed back then.
+    fn get_price_at(
+        &self,
+        identifier: &str,
+        timestamp: NaiveDateTime,
+    ) -> Result<Option<Rappen>, Error>;
+
+    /// All prices of a product, oldest first.
+    fn get_price_history(&self, identifier: &str) -> Result<Vec<ProductPrice>, Error>;
+}
+
+/// Ends the current price period of a product and starts a new one, unless the price didn't change.
+pub(crate) fn record_price(
+    database_connection: &SqliteConnection,
+    identifier: &str,
+    price: Rappen,
+    timestamp: NaiveDateTime,
+) -> Result<(), Error> {
+    let current_prices = product_prices::table
+        .filter(product_prices::product_identifier.eq(identifier))
+        .filter(product_prices::valid_until.is_null());
+
+    let current_price = current_prices
+        .select(product_prices::price)
+        .first::<Rappen>(database_connection)
+        .optional()
+        .map_err(Error::Database)?;
+
+    if current_price == Some(price) {
+        return Ok(());
+    }
+
+    diesel::update(current_prices)
+        .set(product_prices::valid_until.eq(timestamp))
+        .execute(database_connection)
+        .map_err(Error::Database)?;
+
+    diesel::insert_into(product_prices::table)
+        .values(NewProductPrice {
+            product_identifier: identifier,
+            price,
+            valid_from: timestamp,
+        })
+        .execute(database_connection)
+        .map(|_| ())
+        .map_err(Error::Database)
+}
+
+pub(crate) fn get_effective_price(
+    database_connection: &SqliteConnection,
+    identifier: &str,
+    timestamp: NaiveDateTime,
+) -> Result<Option<Rappen>, Error> {
+    product_prices::table
+        .filter(product_prices::product_identifier.eq(identifier))
+        .filter(product_prices::valid_from.le(timestamp))
+        .filter(
+            product_prices::valid_until
+                .is_null()
+                .or(product_prices::valid_until.gt(timestamp)),
+        )
+        .order(product_prices::valid_from.desc())
+        .select(product_prices::price)
+        .first::<Rappen>(database_connection)
+        .optional()
+        .map_err(Error::Database)
 }
 
 pub struct ProductServiceImpl<'a> {
@@ -24,26 +102,125 @@ impl<'a> ProductServiceImpl<'a> {
             database_connection,
         }
     }
+
+    fn get_existing_product(&self, identifier: &str) -> Result<Product, Error> {
+        self.get_product_with_identifier(identifier)?
+            .ok_or_else(|| Error::NotFound(format!("Product \"{}\"", identifier)))
+    }
 }
 
 impl ProductService for ProductServiceImpl<'_> {
-    fn get_available_products(&self) -> Result<Vec<Product>, ()> {
+    fn get_available_products(&self) -> Result<Vec<Product>, Error> {
         products_dsl
+            .filter(products::available.eq(true))
+            .order(products::identifier)
             .load::<Product>(self.database_connection)
-            .map_err(|_| ())
+            .map_err(Error::Database)
     }
 
-    fn get_product_with_identifier(&self, identifier: &str) -> Result<Option<Product>, ()> {
+    fn get_product_with_identifier(&self, identifier: &str) -> Result<Option<Product>, Error> {
         products_dsl
             .find(identifier)
             .first::<Product>(self.database_connection)
             .optional()
-            .map_err(|_| ())
+            .map_err(Error::Database)
+    }
+
+    fn restock_product(&self, identifier: &str, quantity: u32) -> Result<Product, Error> {
+        self.database_connection.transaction(|| {
+            let mut product = self.get_existing_product(identifier)?;
+
+            let stock = product.stock.unwrap_or(0) + quantity as i32;
+            diesel::update(&product)
+                .set(products::stock.eq(stock))
+                .execute(self.database_connection)
+                .map_err(Error::Database)?;
+
+            product.stock = Some(stock);
+            Ok(product)
+        })
+    }
+
+    fn add_product(&self, product: &Product) -> Result<(), Error> {
+        self.database_connection.transaction(|| {
+            if self
+                .get_product_with_identifier(&product.identifier)?
+                .is_some()
+            {
+                return Err(Error::Validation(format!(
+                    "There already is a product called /{}",
+                    product.identifier
+                )));
+            }
+
+            diesel::insert_into(products::table)
+                .values(product)
+                .execute(self.database_connection)
+                .map_err(Error::Database)?;
+
+            record_price(
+                self.database_connection,
+                &product.identifier,
+                product.price,
+                Utc::now().naive_utc(),
+            )
+        })
+    }
+
+    fn set_price(&self, identifier: &str, price: Rappen) -> Result<Product, Error> {
+        self.database_connection.transaction(|| {
+            let mut product = self.get_existing_product(identifier)?;
+
+            diesel::update(&product)
+                .set(products::price.eq(price))
+                .execute(self.database_connection)
+                .map_err(Error::Database)?;
+            record_price(
+                self.database_connection,
+                identifier,
+                price,
+                Utc::now().naive_utc(),
+            )?;
+
+            product.price = price;
+            Ok(product)
+        })
+    }
+
+    fn rename_product(&self, identifier: &str, name: &str) -> Result<Product, Error> {
+        self.database_connection.transaction(|| {
+            let mut product = self.get_existing_product(identifier)?;
+
+            diesel::update(&product)
+                .set(products::name.eq(name))
+                .execute(self.database_connection)
+                .map_err(Error::Database)?;
+
+            product.name = name.to_string();
+            Ok(product)
+        })
+    }
+
+    fn get_price_at(
+        &self,
+        identifier: &str,
+        timestamp: NaiveDateTime,
+    ) -> Result<Option<Rappen>, Error> {
+        get_effective_price(self.database_connection, identifier, timestamp)
+    }
+
+    fn get_price_history(&self, identifier: &str) -> Result<Vec<ProductPrice>, Error> {
+        product_prices::table
+            .filter(product_prices::product_identifier.eq(identifier))
+            .order((product_prices::valid_from, product_prices::id))
+            .load::<ProductPrice>(self.database_connection)
+            .map_err(Error::Database)
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use chrono::Duration;
     use diesel::Connection;
 
     use products::dsl::products as products_dsl;
@@ -70,6 +247,8 @@ mod tests {
             identifier: "foo".to_string(),
             name: "bar".to_string(),
             price: 120,
+            available: true,
+            stock: None,
         };
 
         let database_connection = setup_in_memory_database();
@@ -83,4 +262,178 @@ mod tests {
         let result = product_service.get_product_with_identifier("foo");
         assert_eq!(Ok(Some(product)), result);
     }
+
+    #[test]
+    fn restock_product() {
+        let database_connection = setup_in_memory_database();
+        diesel::insert_into(products::table)
+            .values(&Product {
+                identifier: "foo".to_string(),
+                name: "bar".to_string(),
+                price: 120,
+                available: true,
+                stock: None,
+            })
+            .execute(&database_connection)
+            .unwrap();
+
+        let product_service = ProductServiceImpl::new(&database_connection);
+
+        assert_eq!(
+            Some(24),
+            product_service.restock_product("foo", 24).unwrap().stock
+        );
+        assert_eq!(
+            Some(30),
+            product_service.restock_product("foo", 6).unwrap().stock
+        );
+        assert_eq!(
+            Some(30),
+            product_service
+                .get_product_with_identifier("foo")
+                .unwrap()
+                .unwrap()
+                .stock
+        );
+        assert_eq!(
+            Err(Error::NotFound("Product \"baz\"".to_string())),
+            product_service.restock_product("baz", 1)
+        );
+    }
+
+    #[test]
+    fn restock_keeps_oversold_units() {
+        let database_connection = setup_in_memory_database();
+        diesel::insert_into(products::table)
+            .values(&Product {
+                identifier: "foo".to_string(),
+                name: "bar".to_string(),
+                price: 120,
+                available: true,
+                stock: Some(-3),
+            })
+            .execute(&database_connection)
+            .unwrap();
+
+        let product_service = ProductServiceImpl::new(&database_connection);
+
+        assert_eq!(
+            Some(21),
+            product_service.restock_product("foo", 24).unwrap().stock
+        );
+    }
+
+    #[test]
+    fn add_and_edit_product() {
+        let database_connection = setup_in_memory_database();
+
+        let product = Product {
+            identifier: "tea".to_string(),
+            name: "Black tea".to_string(),
+            price: 150,
+            available: true,
+            stock: None,
+        };
+
+        let product_service = ProductServiceImpl::new(&database_connection);
+        product_service.add_product(&product).unwrap();
+        assert_eq!(
+            Err(Error::Validation(
+                "There already is a product called /tea".to_string()
+            )),
+            product_service.add_product(&product)
+        );
+
+        product_service.set_price("tea", 180).unwrap();
+        product_service.rename_product("tea", "Green tea").unwrap();
+
+        let product = product_service
+            .get_product_with_identifier("tea")
+            .unwrap()
+            .unwrap();
+        assert_eq!(180, product.price);
+        assert_eq!("Green tea", product.name);
+
+        assert_eq!(
+            Err(Error::NotFound("Product \"coffee\"".to_string())),
+            product_service.set_price("coffee", 100)
+        );
+    }
+
+    #[test]
+    fn price_changes_are_recorded() {
+        let database_connection = setup_in_memory_database();
+
+        let product_service = ProductServiceImpl::new(&database_connection);
+        product_service
+            .add_product(&Product {
+                identifier: "coffee".to_string(),
+                name: "Coffee".to_string(),
+                price: 100,
+                available: true,
+                stock: None,
+            })
+            .unwrap();
+        let before_change = Utc::now().naive_utc();
+
+        product_service.set_price("coffee", 120).unwrap();
+        product_service.set_price("coffee", 120).unwrap();
+
+        let history = product_service.get_price_history("coffee").unwrap();
+        assert_eq!(
+            vec![100, 120],
+            history.iter().map(|price| price.price).collect::<Vec<_>>()
+        );
+        assert_eq!(Some(history[1].valid_from), history[0].valid_until);
+        assert_eq!(None, history[1].valid_until);
+
+        assert_eq!(
+            Ok(Some(100)),
+            product_service.get_price_at("coffee", before_change)
+        );
+        assert_eq!(
+            Ok(Some(120)),
+            product_service.get_price_at("coffee", Utc::now().naive_utc())
+        );
+        assert_eq!(
+            Ok(None),
+            product_service.get_price_at("coffee", history[0].valid_from - Duration::seconds(1))
+        );
+    }
+
+    #[test]
+    fn get_available_products_skips_unavailable_products() {
+        let database_connection = setup_in_memory_database();
+        diesel::insert_into(products::table)
+            .values(&vec![
+                Product {
+                    identifier: "foo".to_string(),
+                    name: "bar".to_string(),
+                    price: 120,
+                    available: true,
+                    stock: None,
+                },
+                Product {
+                    identifier: "baz".to_string(),
+                    name: "qux".to_string(),
+                    price: 200,
+                    available: false,
+                    stock: None,
+                },
+            ])
+            .execute(&database_connection)
+            .unwrap();
+
+        let product_service = ProductServiceImpl::new(&database_connection);
+
+        let products = product_service.get_available_products().unwrap();
+        assert_eq!(1, products.len());
+        assert_eq!("foo", products[0].identifier);
+
+        let archived_product = product_service
+            .get_product_with_identifier("baz")
+            .unwrap()
+            .unwrap();
+        assert!(!archived_product.available);
+    }
 }
@@ -0,0 +1,218 @@
+use chrono::{Datelike, Months, NaiveDate, NaiveDateTime};
+use diesel::expression::functions::aggregate_folding::sum;
+use diesel::SqliteConnection;
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+#[cfg(test)]
+use mockiato::mockable;
+
+use transactions::dsl::transactions as transactions_dsl;
+use users::dsl::users as users_dsl;
+
+use crate::error::Error;
+use crate::models::{Rappen, Statement, Transaction, User};
+use crate::schema::{transactions, users};
+
+#[cfg_attr(test, mockable)]
+pub trait StatementService {
+    /// Creates the statement for the month starting at the given day.
+    fn get_statement(&self, user: &User, month: NaiveDate) -> Result<Statement, Error>;
+
+    /// Creates the statements of all users for the month starting at the given day, sorted by name.
+    fn get_statements(&self, month: NaiveDate) -> Result<Vec<Statement>, Error>;
+}
+
+pub struct StatementServiceImpl<'a> {
+    database_connection: &'a SqliteConnection,
+}
+
+impl<'a> StatementServiceImpl<'a> {
+    pub fn new(database_connection: &'a SqliteConnection) -> Self {
+        Self {
+            database_connection,
+        }
+    }
+
+    fn get_balance_before(&self, user: &User, timestamp: NaiveDateTime) -> Result<Rappen, Error> {
+        transactions_dsl
+            .filter(transactions::user.eq(&user.id))
+            .filter(transactions::timestamp.lt(timestamp))
+            .select(sum(transactions::amount))
+            .first::<Option<i64>>(self.database_connection)
+            .map(|amount| amount.unwrap_or(0) as Rappen)
+            .map_err(Error::Database)
+    }
+}
+
+impl StatementService for StatementServiceImpl<'_> {
+    fn get_statement(&self, user: &User, month: NaiveDate) -> Result<Statement, Error> {
+        let month = month.with_day(1).unwrap_or(month);
+        let next_month = month
+            .checked_add_months(Months::new(1))
+            .ok_or_else(|| Error::Validation(format!("Invalid month {}", month)))?;
+
+        let start = month.and_hms_opt(0, 0, 0).unwrap();
+        let end = next_month.and_hms_opt(0, 0, 0).unwrap();
+
+        let opening_balance = self.get_balance_before(user, start)?;
+
+        let transactions = transactions_dsl
+            .filter(transactions::user.eq(&user.id))
+            .filter(transactions::timestamp.ge(start))
+            .filter(transactions::timestamp.lt(end))
+            .order((transactions::timestamp, transactions::id))
+            .load::<Transaction>(self.database_connection)
+            .map_err(Error::Database)?;
+
+        let deposits = transactions
+            .iter()
+            .map(|transaction| transaction.amount)
+            .filter(|amount| *amount > 0)
+            .sum::<Rappen>();
+        let consumption = -transactions
+            .iter()
+            .map(|transaction| transaction.amount)
+            .filter(|amount| *amount < 0)
+            .sum::<Rappen>();
+
+        Ok(Statement {
+            user: user.clone(),
+            month,
+            opening_balance,
+            transactions,
+            deposits,
+            consumption,
+            closing_balance: opening_balance + deposits - consumption,
+        })
+    }
+
+    fn get_statements(&self, month: NaiveDate) -> Result<Vec<Statement>, Error> {
+        users_dsl
+            .select((users::id, users::name))
+            .order((users::name, users::id))
+            .load::<User>(self.database_connection)
+            .map_err(Error::Database)?
+            .iter()
+            .map(|user| self.get_statement(user, month))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::NewTransaction;
+    use crate::test_utils::*;
+
+    use super::*;
+
+    fn timestamp(year: i32, month: u32, day: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(year, month, day)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn get_statement() {
+        let database_connection = setup_in_memory_database();
+
+        let user = User {
+            id: "foo".to_string(),
+            name: "Anna".to_string(),
+        };
+        diesel::insert_into(users::table)
+            .values(&user)
+            .execute(&database_connection)
+            .unwrap();
+
+        diesel::insert_into(transactions::table)
+            .values(&vec![
+                NewTransaction {
+                    timestamp: timestamp(2026, 8, 31),
+                    ..NewTransaction::new(1000, &user)
+                },
+                NewTransaction {
+                    timestamp: timestamp(2026, 9, 1),
+                    product_name: Some("Coffee".to_string()),
+                    ..NewTransaction::new(-150, &user)
+                },
+                NewTransaction {
+                    timestamp: timestamp(2026, 9, 15),
+                    ..NewTransaction::new(2000, &user)
+                },
+                NewTransaction {
+                    timestamp: timestamp(2026, 9, 30),
+                    product_name: Some("a coke".to_string()),
+                    ..NewTransaction::new(-420, &user)
+                },
+                NewTransaction {
+                    timestamp: timestamp(2026, 10, 1),
+                    ..NewTransaction::new(500, &user)
+                },
+            ])
+            .execute(&database_connection)
+            .unwrap();
+
+        let statement_service = StatementServiceImpl::new(&database_connection);
+        let statement = statement_service
+            .get_statement(&user, NaiveDate::from_ymd_opt(2026, 9, 10).unwrap())
+            .unwrap();
+
+        assert_eq!(
+            NaiveDate::from_ymd_opt(2026, 9, 1).unwrap(),
+            statement.month
+        );
+        assert_eq!(1000, statement.opening_balance);
+        assert_eq!(
+            vec![-150, 2000, -420],
+            statement
+                .transactions
+                .iter()
+                .map(|transaction| transaction.amount)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(2000, statement.deposits);
+        assert_eq!(570, statement.consumption);
+        assert_eq!(2430, statement.closing_balance);
+    }
+
+    #[test]
+    fn get_statements_of_all_users() {
+        let database_connection = setup_in_memory_database();
+
+        let anna = User {
+            id: "foo".to_string(),
+            name: "Anna".to_string(),
+        };
+        let bert = User {
+            id: "bar".to_string(),
+            name: "Bert".to_string(),
+        };
+        diesel::insert_into(users::table)
+            .values(&vec![bert.clone(), anna.clone()])
+            .execute(&database_connection)
+            .unwrap();
+
+        diesel::insert_into(transactions::table)
+            .values(&NewTransaction {
+                timestamp: timestamp(2026, 9, 2),
+                ..NewTransaction::new(-150, &bert)
+            })
+            .execute(&database_connection)
+            .unwrap();
+
+        let statement_service = StatementServiceImpl::new(&database_connection);
+        let statements = statement_service
+            .get_statements(NaiveDate::from_ymd_opt(2026, 9, 1).unwrap())
+            .unwrap();
+
+        assert_eq!(
+            vec![anna, bert],
+            statements
+                .iter()
+                .map(|statement| statement.user.clone())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(0, statements[0].closing_balance);
+        assert_eq!(-150, statements[1].closing_balance);
+    }
+}
@@ -1,21 +1,59 @@
 use chrono::Utc;
 use diesel::result::DatabaseErrorKind;
 use diesel::result::Error::DatabaseError;
-use diesel::SqliteConnection;
-use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+use diesel::{Connection, SqliteConnection};
+use diesel::{ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl};
 #[cfg(test)]
 use mockiato::mockable;
 
 use transactions::dsl::transactions as transactions_dsl;
 
-use crate::models::{Product, Rappen, Transaction, User};
-use crate::schema::transactions;
+use crate::error::Error;
+use crate::models::{
+    NewExpense, NewTransaction, Product, Rappen, Transaction, TransactionKind, User,
+};
+use crate::schema::{expenses, products, transactions};
+use crate::services::product_service::get_effective_price;
+
+no_arg_sql_function!(last_insert_rowid, diesel::sql_types::Integer);
 
 #[cfg_attr(test, mockable)]
 pub trait TransactionService {
-    fn register_product_transaction(&self, product: &Product, sender: &User) -> Result<(), ()>;
+    fn register_product_transaction(
+        &self,
+        product: &Product,
+        quantity: u32,
+        sender: &User,
+    ) -> Result<(), Error>;
+
+    fn register_amount_transaction(
+        &self,
+        amount: Rappen,
+        kind: TransactionKind,
+        sender: &User,
+    ) -> Result<(), Error>;
+
+    fn register_transfer(
+        &self,
+        amount: Rappen,
+        sender: &User,
+        recipient: &User,
+    ) -> Result<(), Error>;
+
+    /// Credits the sender for supplies bought for the kitty and records
+    /// the expense against the kitty's cash.
+    fn register_expense(
+        &self,
+        amount: Rappen,
+        description: &str,
+        sender: &User,
+    ) -> Result<(), Error>;
+
+    fn get_last_transaction(&self, user: &User) -> Result<Option<Transaction>, Error>;
+
+    fn get_last_transactions(&self, user: &User, count: u32) -> Result<Vec<Transaction>, Error>;
 
-    fn register_amount_transaction(&self, amount: Rappen, sender: &User) -> Result<(), ()>;
+    fn delete_transaction(&self, transaction: &Transaction) -> Result<(), Error>;
 }
 
 pub struct TransactionServiceImpl<'a> {
@@ -29,36 +67,486 @@ impl<'a> TransactionServiceImpl<'a> {
         }
     }
 
-    fn insert_transaction(&self, transaction: Transaction) -> Result<(), ()> {
+    fn insert_transaction(&self, transaction: NewTransaction) -> Result<(), Error> {
         diesel::insert_into(transactions::table)
             .values(transaction)
             .execute(self.database_connection)
             .map(|_| ())
-            .map_err(|_| ())
+            .map_err(Error::Database)
+    }
+
+    /// Adjusts the stock of a product. Products without a tracked stock are left untouched.
+    fn adjust_stock(&self, product_identifier: &str, difference: i32) -> Result<(), Error> {
+        diesel::update(products::table.find(product_identifier))
+            .filter(products::stock.is_not_null())
+            .set(products::stock.eq(products::stock + difference))
+            .execute(self.database_connection)
+            .map(|_| ())
+            .map_err(Error::Database)
     }
 }
 
 impl TransactionService for TransactionServiceImpl<'_> {
-    fn register_product_transaction(&self, product: &Product, sender: &User) -> Result<(), ()> {
-        self.insert_transaction(Transaction {
-            amount: -product.price,
-            timestamp: Utc::now().naive_utc(),
-            user: sender.id.clone(),
-            product_name: Some(product.name.clone()),
+    fn register_product_transaction(
+        &self,
+        product: &Product,
+        quantity: u32,
+        sender: &User,
+    ) -> Result<(), Error> {
+        self.database_connection.transaction(|| {
+            let transaction = NewTransaction::new(0, sender);
+            let price = get_effective_price(
+                self.database_connection,
+                &product.identifier,
+                transaction.timestamp,
+            )?
+            .unwrap_or(product.price);
+
+            self.insert_transaction(NewTransaction {
+                amount: -price * quantity as Rappen,
+                product_name: Some(product.name.clone()),
+                product_identifier: Some(product.identifier.clone()),
+                product_price: Some(price),
+                quantity: Some(quantity as i32),
+                kind: TransactionKind::Purchase,
+                ..transaction
+            })?;
+
+            self.adjust_stock(&product.identifier, -(quantity as i32))
         })
     }
 
-    fn register_amount_transaction(&self, amount: Rappen, sender: &User) -> Result<(), ()> {
-        self.insert_transaction(Transaction {
-            amount,
-            timestamp: Utc::now().naive_utc(),
-            user: sender.id.clone(),
-            product_name: None,
+    fn register_amount_transaction(
+        &self,
+        amount: Rappen,
+        kind: TransactionKind,
+        sender: &User,
+    ) -> Result<(), Error> {
+        self.insert_transaction(NewTransaction {
+            kind,
+            ..NewTransaction::new(amount, sender)
+        })
+    }
+
+    fn register_transfer(
+        &self,
+        amount: Rappen,
+        sender: &User,
+        recipient: &User,
+    ) -> Result<(), Error> {
+        let timestamp = Utc::now().naive_utc();
+
+        self.database_connection.transaction(|| {
+            self.insert_transaction(NewTransaction {
+                timestamp,
+                counterparty: Some(recipient.id.clone()),
+                kind: TransactionKind::Transfer,
+                ..NewTransaction::new(-amount, sender)
+            })?;
+
+            self.insert_transaction(NewTransaction {
+                timestamp,
+                counterparty: Some(sender.id.clone()),
+                kind: TransactionKind::Transfer,
+                ..NewTransaction::new(amount, recipient)
+            })
+        })
+    }
+
+    fn register_expense(
+        &self,
+        amount: Rappen,
+        description: &str,
+        sender: &User,
+    ) -> Result<(), Error> {
+        self.database_connection.transaction(|| {
+            self.insert_transaction(NewTransaction {
+                kind: TransactionKind::ExpenseReimbursement,
+                ..NewTransaction::new(amount, sender)
+            })?;
+
+            let transaction_id = diesel::select(last_insert_rowid)
+                .get_result::<i32>(self.database_connection)
+                .map_err(Error::Database)?;
+
+            diesel::insert_into(expenses::table)
+                .values(&NewExpense {
+                    transaction_id,
+                    amount,
+                    description,
+                })
+                .execute(self.database_connection)
+                .map(|_| ())
+                .map_err(Error::Database)
+        })
+    }
+
+    fn get_last_transaction(&self, user: &User) -> Result<Option<Transaction>, Error> {
+        transactions_dsl
+            .filter(transactions::user.eq(&user.id))
+            .order((transactions::timestamp.desc(), transactions::id.desc()))
+            .first::<Transaction>(self.database_connection)
+            .optional()
+            .map_err(Error::Database)
+    }
+
+    fn get_last_transactions(&self, user: &User, count: u32) -> Result<Vec<Transaction>, Error> {
+        transactions_dsl
+            .filter(transactions::user.eq(&user.id))
+            .order((transactions::timestamp.desc(), transactions::id.desc()))
+            .limit(count.into())
+            .load::<Transaction>(self.database_connection)
+            .map_err(Error::Database)
+    }
+
+    fn delete_transaction(&self, transaction: &Transaction) -> Result<(), Error> {
+        self.database_connection.transaction(|| {
+            diesel::delete(expenses::table.filter(expenses::transaction_id.eq(transaction.id)))
+                .execute(self.database_connection)
+                .map_err(Error::Database)?;
+
+            diesel::delete(transaction)
+                .execute(self.database_connection)
+                .map_err(Error::Database)?;
+
+            match (&transaction.product_identifier, transaction.quantity) {
+                (Some(product_identifier), Some(quantity)) => {
+                    self.adjust_stock(product_identifier, quantity)
+                }
+                _ => Ok(()),
+            }
         })
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use chrono::Duration;
+
+    use crate::schema::{products, users};
+    use crate::services::product_service::record_price;
+    use crate::test_utils::*;
+
     use super::*;
+
+    fn insert_user(database_connection: &SqliteConnection, id: &str) -> User {
+        let user = User {
+            id: id.to_string(),
+            name: "bar".to_string(),
+        };
+
+        diesel::insert_into(users::table)
+            .values(&user)
+            .execute(database_connection)
+            .unwrap();
+
+        user
+    }
+
+    #[test]
+    fn get_last_transaction_without_transactions() {
+        let database_connection = setup_in_memory_database();
+        let user = insert_user(&database_connection, "foo");
+
+        let transaction_service = TransactionServiceImpl::new(&database_connection);
+
+        let result = transaction_service.get_last_transaction(&user);
+        assert_eq!(Ok(None), result);
+    }
+
+    #[test]
+    fn get_last_transaction_of_user() {
+        let database_connection = setup_in_memory_database();
+        let user = insert_user(&database_connection, "foo");
+        let other_user = insert_user(&database_connection, "bar");
+
+        let now = Utc::now().naive_utc();
+        diesel::insert_into(transactions::table)
+            .values(&vec![
+                NewTransaction {
+                    timestamp: now - Duration::minutes(1),
+                    ..NewTransaction::new(100, &user)
+                },
+                NewTransaction {
+                    timestamp: now,
+                    product_name: Some("a coke".to_string()),
+                    ..NewTransaction::new(-420, &user)
+                },
+                NewTransaction {
+                    timestamp: now + Duration::minutes(1),
+                    ..NewTransaction::new(200, &other_user)
+                },
+            ])
+            .execute(&database_connection)
+            .unwrap();
+
+        let transaction_service = TransactionServiceImpl::new(&database_connection);
+
+        let transaction = transaction_service
+            .get_last_transaction(&user)
+            .unwrap()
+            .unwrap();
+        assert_eq!(-420, transaction.amount);
+        assert_eq!(Some("a coke".to_string()), transaction.product_name);
+    }
+
+    #[test]
+    fn get_last_transactions_is_limited_and_newest_first() {
+        let database_connection = setup_in_memory_database();
+        let user = insert_user(&database_connection, "foo");
+
+        let now = Utc::now().naive_utc();
+        diesel::insert_into(transactions::table)
+            .values(
+                &(1..=3)
+                    .map(|minutes| NewTransaction {
+                        timestamp: now + Duration::minutes(minutes.into()),
+                        ..NewTransaction::new(minutes * 100, &user)
+                    })
+                    .collect::<Vec<_>>(),
+            )
+            .execute(&database_connection)
+            .unwrap();
+
+        let transaction_service = TransactionServiceImpl::new(&database_connection);
+
+        let amounts = transaction_service
+            .get_last_transactions(&user, 2)
+            .unwrap()
+            .iter()
+            .map(|transaction| transaction.amount)
+            .collect::<Vec<_>>();
+        assert_eq!(vec![300, 200], amounts);
+    }
+
+    #[test]
+    fn register_product_transaction_links_product() {
+        let database_connection = setup_in_memory_database();
+        let user = insert_user(&database_connection, "foo");
+
+        let product = Product {
+            identifier: "coke".to_string(),
+            name: "a coke".to_string(),
+            price: 420,
+            available: true,
+            stock: None,
+        };
+        diesel::insert_into(products::table)
+            .values(&product)
+            .execute(&database_connection)
+            .unwrap();
+
+        let transaction_service = TransactionServiceImpl::new(&database_connection);
+        transaction_service
+            .register_product_transaction(&product, 3, &user)
+            .unwrap();
+
+        let transaction = transaction_service
+            .get_last_transaction(&user)
+            .unwrap()
+            .unwrap();
+        assert_eq!(-1260, transaction.amount);
+        assert_eq!(Some("coke".to_string()), transaction.product_identifier);
+        assert_eq!(Some("a coke".to_string()), transaction.product_name);
+        assert_eq!(Some(420), transaction.product_price);
+        assert_eq!(Some(3), transaction.quantity);
+        assert_eq!(TransactionKind::Purchase, transaction.kind);
+    }
+
+    #[test]
+    fn register_product_transaction_uses_effective_price() {
+        let database_connection = setup_in_memory_database();
+        let user = insert_user(&database_connection, "foo");
+
+        let product = Product {
+            identifier: "coke".to_string(),
+            name: "a coke".to_string(),
+            price: 420,
+            available: true,
+            stock: None,
+        };
+        diesel::insert_into(products::table)
+            .values(&product)
+            .execute(&database_connection)
+            .unwrap();
+        record_price(
+            &database_connection,
+            "coke",
+            450,
+            Utc::now().naive_utc() - Duration::minutes(1),
+        )
+        .unwrap();
+
+        let transaction_service = TransactionServiceImpl::new(&database_connection);
+        transaction_service
+            .register_product_transaction(&product, 2, &user)
+            .unwrap();
+
+        let transaction = transaction_service
+            .get_last_transaction(&user)
+            .unwrap()
+            .unwrap();
+        assert_eq!(-900, transaction.amount);
+        assert_eq!(Some(450), transaction.product_price);
+    }
+
+    #[test]
+    fn product_transactions_update_stock() {
+        let database_connection = setup_in_memory_database();
+        let user = insert_user(&database_connection, "foo");
+
+        let product = Product {
+            identifier: "coke".to_string(),
+            name: "a coke".to_string(),
+            price: 420,
+            available: true,
+            stock: Some(10),
+        };
+        diesel::insert_into(products::table)
+            .values(&product)
+            .execute(&database_connection)
+            .unwrap();
+
+        let stock = || {
+            products::table
+                .find("coke")
+                .select(products::stock)
+                .first::<Option<i32>>(&database_connection)
+                .unwrap()
+        };
+
+        let transaction_service = TransactionServiceImpl::new(&database_connection);
+        transaction_service
+            .register_product_transaction(&product, 3, &user)
+            .unwrap();
+        assert_eq!(Some(7), stock());
+
+        let transaction = transaction_service
+            .get_last_transaction(&user)
+            .unwrap()
+            .unwrap();
+        transaction_service
+            .delete_transaction(&transaction)
+            .unwrap();
+        assert_eq!(Some(10), stock());
+    }
+
+    #[test]
+    fn register_transfer_books_both_sides() {
+        let database_connection = setup_in_memory_database();
+        let sender = insert_user(&database_connection, "foo");
+        let recipient = insert_user(&database_connection, "bar");
+
+        let transaction_service = TransactionServiceImpl::new(&database_connection);
+        transaction_service
+            .register_transfer(250, &sender, &recipient)
+            .unwrap();
+
+        let sender_transaction = transaction_service
+            .get_last_transaction(&sender)
+            .unwrap()
+            .unwrap();
+        assert_eq!(-250, sender_transaction.amount);
+        assert_eq!(Some(recipient.id.clone()), sender_transaction.counterparty);
+        assert_eq!(TransactionKind::Transfer, sender_transaction.kind);
+
+        let recipient_transaction = transaction_service
+            .get_last_transaction(&recipient)
+            .unwrap()
+            .unwrap();
+        assert_eq!(250, recipient_transaction.amount);
+        assert_eq!(Some(sender.id.clone()), recipient_transaction.counterparty);
+    }
+
+    #[test]
+    fn register_transfer_to_unknown_user_is_rolled_back() {
+        let database_connection = setup_in_memory_database();
+        diesel::sql_query("PRAGMA foreign_keys = ON")
+            .execute(&database_connection)
+            .unwrap();
+
+        let sender = insert_user(&database_connection, "foo");
+        let recipient = User {
+            id: "unknown".to_string(),
+            name: "baz".to_string(),
+        };
+
+        let transaction_service = TransactionServiceImpl::new(&database_connection);
+        transaction_service
+            .register_transfer(250, &sender, &recipient)
+            .unwrap_err();
+
+        let transactions = transactions_dsl
+            .load::<Transaction>(&database_connection)
+            .unwrap();
+        assert!(transactions.is_empty());
+    }
+
+    #[test]
+    fn delete_transaction() {
+        let database_connection = setup_in_memory_database();
+        let user = insert_user(&database_connection, "foo");
+
+        let transaction_service = TransactionServiceImpl::new(&database_connection);
+        transaction_service
+            .register_amount_transaction(100, TransactionKind::Deposit, &user)
+            .unwrap();
+        transaction_service
+            .register_amount_transaction(200, TransactionKind::Correction, &user)
+            .unwrap();
+
+        let transaction = transaction_service
+            .get_last_transaction(&user)
+            .unwrap()
+            .unwrap();
+        transaction_service
+            .delete_transaction(&transaction)
+            .unwrap();
+
+        let transactions = transactions_dsl
+            .load::<Transaction>(&database_connection)
+            .unwrap();
+        assert_eq!(1, transactions.len());
+        assert_eq!(100, transactions[0].amount);
+    }
+
+    #[test]
+    fn register_expense_credits_sender() {
+        let database_connection = setup_in_memory_database();
+        let user = insert_user(&database_connection, "foo");
+
+        let transaction_service = TransactionServiceImpl::new(&database_connection);
+        transaction_service
+            .register_expense(2490, "coffee beans", &user)
+            .unwrap();
+
+        let transaction = transaction_service
+            .get_last_transaction(&user)
+            .unwrap()
+            .unwrap();
+        assert_eq!(2490, transaction.amount);
+        assert_eq!(TransactionKind::ExpenseReimbursement, transaction.kind);
+
+        let expenses = expenses::table
+            .select((
+                expenses::transaction_id,
+                expenses::amount,
+                expenses::description,
+            ))
+            .load::<(i32, Rappen, String)>(&database_connection)
+            .unwrap();
+        assert_eq!(
+            vec![(transaction.id, 2490, "coffee beans".to_string())],
+            expenses
+        );
+
+        transaction_service
+            .delete_transaction(&transaction)
+            .unwrap();
+        let count = expenses::table
+            .count()
+            .get_result::<i64>(&database_connection)
+            .unwrap();
+        assert_eq!(0, count);
+    }
 }
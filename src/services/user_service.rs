@@ -1,18 +1,31 @@
 use diesel::result::DatabaseErrorKind;
 use diesel::result::Error::DatabaseError;
 use diesel::SqliteConnection;
-use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+use diesel::{ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl};
 #[cfg(test)]
 use mockiato::mockable;
 
 use users::dsl::users as users_dsl;
 
-use crate::models::User;
+use crate::error::Error;
+use crate::models::{Rappen, Role, User};
 use crate::schema::users;
 
 #[cfg_attr(test, mockable)]
 pub trait UserService {
-    fn update_user(&self, user: &User) -> Result<(), ()>;
+    fn update_user(&self, user: &User) -> Result<(), Error>;
+
+    fn get_user_with_name(&self, name: &str) -> Result<User, Error>;
+
+    /// Unknown users are members.
+    fn get_role(&self, user: &User) -> Result<Role, Error>;
+
+    fn set_role(&self, user: &User, role: Role) -> Result<(), Error>;
+
+    /// The personal credit limit of a user, if there is one.
+    fn get_credit_limit(&self, user: &User) -> Result<Option<Rappen>, Error>;
+
+    fn set_credit_limit(&self, user: &User, limit: Option<Rappen>) -> Result<(), Error>;
 }
 
 pub struct UserServiceImpl<'a> {
@@ -28,7 +41,7 @@ impl<'a> UserServiceImpl<'a> {
 }
 
 impl UserService for UserServiceImpl<'_> {
-    fn update_user(&self, user: &User) -> Result<(), ()> {
+    fn update_user(&self, user: &User) -> Result<(), Error> {
         let User { id, name } = user;
 
         match diesel::update(users_dsl.find(id))
@@ -37,14 +50,76 @@ impl UserService for UserServiceImpl<'_> {
         {
             Ok(0) => (),
             Ok(_) => return Ok(()),
-            Err(_) => return Err(()),
+            Err(error) => return Err(Error::Database(error)),
         }
 
         diesel::insert_into(users::table)
             .values(user)
             .execute(self.database_connection)
             .map(|_| ())
-            .map_err(|_| ())
+            .map_err(Error::Database)
+    }
+
+    fn get_user_with_name(&self, name: &str) -> Result<User, Error> {
+        // SQLite's lower() only folds ASCII, so names are compared here.
+        let lowercase_name = name.to_lowercase();
+        let mut matching_users: Vec<User> = users_dsl
+            .select((users::id, users::name))
+            .load::<User>(self.database_connection)
+            .map_err(Error::Database)?
+            .into_iter()
+            .filter(|user| user.id == name || user.name.to_lowercase() == lowercase_name)
+            .collect();
+
+        match matching_users.len() {
+            0 => Err(Error::NotFound(format!("User \"{}\"", name))),
+            1 => Ok(matching_users.remove(0)),
+            _ => Err(Error::Validation(format!(
+                "There are multiple users called \"{}\"",
+                name
+            ))),
+        }
+    }
+
+    fn get_role(&self, user: &User) -> Result<Role, Error> {
+        let role = users_dsl
+            .find(&user.id)
+            .select(users::role)
+            .first::<String>(self.database_connection)
+            .optional()
+            .map_err(Error::Database)?;
+
+        match role {
+            Some(role) => Role::parse(&role)
+                .ok_or_else(|| Error::Validation(format!("Unknown role \"{}\"", role))),
+            None => Ok(Role::Member),
+        }
+    }
+
+    fn set_role(&self, user: &User, role: Role) -> Result<(), Error> {
+        diesel::update(users_dsl.find(&user.id))
+            .set(users::role.eq(role.as_str()))
+            .execute(self.database_connection)
+            .map(|_| ())
+            .map_err(Error::Database)
+    }
+
+    fn get_credit_limit(&self, user: &User) -> Result<Option<Rappen>, Error> {
+        users_dsl
+            .find(&user.id)
+            .select(users::credit_limit)
+            .first::<Option<Rappen>>(self.database_connection)
+            .optional()
+            .map(Option::flatten)
+            .map_err(Error::Database)
+    }
+
+    fn set_credit_limit(&self, user: &User, limit: Option<Rappen>) -> Result<(), Error> {
+        diesel::update(users_dsl.find(&user.id))
+            .set(users::credit_limit.eq(limit))
+            .execute(self.database_connection)
+            .map(|_| ())
+            .map_err(Error::Database)
     }
 }
 
@@ -73,7 +148,10 @@ mod tests {
 
         user_service.update_user(&user).unwrap();
 
-        let users = users_dsl.load::<User>(&database_connection).unwrap();
+        let users = users_dsl
+            .select((users::id, users::name))
+            .load::<User>(&database_connection)
+            .unwrap();
         assert_eq!(vec![user], users)
     }
 
@@ -96,7 +174,99 @@ mod tests {
         let user_service = UserServiceImpl::new(&database_connection);
         user_service.update_user(&user).unwrap();
 
-        let users = users_dsl.load::<User>(&database_connection).unwrap();
+        let users = users_dsl
+            .select((users::id, users::name))
+            .load::<User>(&database_connection)
+            .unwrap();
         assert_eq!(vec![user], users)
     }
+
+    #[test]
+    fn get_user_with_name_ignores_case() {
+        let database_connection = setup_in_memory_database();
+
+        let user = User {
+            id: "foo".to_string(),
+            name: "Anna".to_string(),
+        };
+
+        diesel::insert_into(users::table)
+            .values(&user)
+            .execute(&database_connection)
+            .unwrap();
+
+        let user_service = UserServiceImpl::new(&database_connection);
+
+        assert_eq!(Ok(user), user_service.get_user_with_name("anna"));
+    }
+
+    #[test]
+    fn get_user_with_name_ignores_case_of_non_ascii_letters() {
+        let database_connection = setup_in_memory_database();
+
+        let user = User {
+            id: "foo".to_string(),
+            name: "Émile".to_string(),
+        };
+
+        diesel::insert_into(users::table)
+            .values(&user)
+            .execute(&database_connection)
+            .unwrap();
+
+        let user_service = UserServiceImpl::new(&database_connection);
+
+        assert_eq!(Ok(user), user_service.get_user_with_name("émile"));
+    }
+
+    #[test]
+    fn get_unknown_user_with_name() {
+        let database_connection = setup_in_memory_database();
+
+        let user_service = UserServiceImpl::new(&database_connection);
+
+        assert_eq!(
+            Err(Error::NotFound("User \"anna\"".to_string())),
+            user_service.get_user_with_name("anna")
+        );
+    }
+
+    #[test]
+    fn set_role() {
+        let database_connection = setup_in_memory_database();
+
+        let user = User {
+            id: "foo".to_string(),
+            name: "Anna".to_string(),
+        };
+
+        let user_service = UserServiceImpl::new(&database_connection);
+        assert_eq!(Ok(Role::Member), user_service.get_role(&user));
+
+        user_service.update_user(&user).unwrap();
+        assert_eq!(Ok(Role::Member), user_service.get_role(&user));
+
+        user_service.set_role(&user, Role::Admin).unwrap();
+        assert_eq!(Ok(Role::Admin), user_service.get_role(&user));
+    }
+
+    #[test]
+    fn set_credit_limit() {
+        let database_connection = setup_in_memory_database();
+
+        let user = User {
+            id: "foo".to_string(),
+            name: "Anna".to_string(),
+        };
+
+        let user_service = UserServiceImpl::new(&database_connection);
+        user_service.update_user(&user).unwrap();
+        assert_eq!(Ok(None), user_service.get_credit_limit(&user));
+
+        user_service.set_credit_limit(&user, Some(2000)).unwrap();
+        assert_eq!(Ok(Some(2000)), user_service.get_credit_limit(&user));
+
+        user_service.set_credit_limit(&user, None).unwrap();
+        assert_eq!(Ok(None), user_service.get_credit_limit(&user));
+    }
 }
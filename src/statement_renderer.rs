@@ -0,0 +1,237 @@
+use chrono::NaiveDate;
+
+use crate::currency_handling::currency_formatter::CurrencyFormatter;
+use crate::error::Error;
+use crate::markdown::{bold, escape};
+use crate::models::{Statement, Transaction};
+use crate::services::statement_service::StatementService;
+
+const STATEMENT_SEPARATOR: &str = "\n\n----------------------------------------\n\n";
+
+/// Renders the month-end statements of all users as plain text, one after the other.
+pub fn render_monthly_statements(
+    statement_service: &dyn StatementService,
+    month: NaiveDate,
+    currency_formatter: &dyn CurrencyFormatter,
+) -> Result<String, Error> {
+    Ok(statement_service
+        .get_statements(month)?
+        .iter()
+        .map(|statement| render_text(statement, currency_formatter))
+        .collect::<Vec<_>>()
+        .join(STATEMENT_SEPARATOR))
+}
+
+/// Renders a statement as plain text, e.g. for emails or the command line.
+pub fn render_text(statement: &Statement, currency_formatter: &dyn CurrencyFormatter) -> String {
+    let mut lines = vec![
+        format_title(statement),
+        String::new(),
+        format!(
+            "Opening balance: {}",
+            currency_formatter.format_amount(statement.opening_balance)
+        ),
+    ];
+    lines.extend(format_transactions(
+        &statement.transactions,
+        currency_formatter,
+    ));
+    lines.push(String::new());
+    lines.extend(format_totals(statement, currency_formatter, |label| {
+        label.to_string()
+    }));
+
+    lines.join("\n")
+}
+
+/// Renders a statement for chats that support Telegram's Markdown.
+pub fn render_markdown(
+    statement: &Statement,
+    currency_formatter: &dyn CurrencyFormatter,
+) -> String {
+    let mut lines = vec![
+        bold(&format_title(statement)),
+        format!(
+            "Opening balance: {}",
+            currency_formatter.format_amount(statement.opening_balance)
+        ),
+    ];
+    let bullet = if statement.transactions.is_empty() {
+        ""
+    } else {
+        "- "
+    };
+    lines.extend(
+        format_transactions(&statement.transactions, currency_formatter)
+            .into_iter()
+            .map(|line| format!("{}{}", bullet, escape(&line))),
+    );
+    lines.extend(format_totals(statement, currency_formatter, bold));
+
+    lines.join("\n")
+}
+
+fn format_title(statement: &Statement) -> String {
+    format!(
+        "Statement for {}, {}",
+        statement.user.name,
+        statement.month.format("%B %Y")
+    )
+}
+
+fn format_transactions(
+    transactions: &[Transaction],
+    currency_formatter: &dyn CurrencyFormatter,
+) -> Vec<String> {
+    if transactions.is_empty() {
+        return vec!["No transactions recorded".to_string()];
+    }
+
+    transactions
+        .iter()
+        .map(|transaction| {
+            format!(
+                "{} - {} ({})",
+                transaction.timestamp.format("%d.%m.%Y %H:%M"),
+                transaction.description(),
+                currency_formatter.format_amount(transaction.amount)
+            )
+        })
+        .collect()
+}
+
+fn format_totals(
+    statement: &Statement,
+    currency_formatter: &dyn CurrencyFormatter,
+    format_label: impl Fn(&str) -> String,
+) -> Vec<String> {
+    vec![
+        format!(
+            "{} {}",
+            format_label("Deposits:"),
+            currency_formatter.format_amount(statement.deposits)
+        ),
+        format!(
+            "{} {}",
+            format_label("Consumption:"),
+            currency_formatter.format_amount(statement.consumption)
+        ),
+        format!(
+            "{} {}",
+            format_label("Closing balance:"),
+            currency_formatter.format_amount(statement.closing_balance)
+        ),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::currency_handling::currency_formatter::CurrencyFormatterImpl;
+    use crate::models::{TransactionKind, User};
+    use crate::services::statement_service::StatementServiceMock;
+
+    use super::*;
+
+    fn statement() -> Statement {
+        Statement {
+            user: User {
+                id: "foo".to_string(),
+                name: "Anna".to_string(),
+            },
+            month: NaiveDate::from_ymd_opt(2026, 9, 1).unwrap(),
+            opening_balance: 1000,
+            transactions: vec![Transaction {
+                id: 1,
+                amount: -300,
+                timestamp: NaiveDate::from_ymd_opt(2026, 9, 2)
+                    .unwrap()
+                    .and_hms_opt(8, 15, 0)
+                    .unwrap(),
+                user: "foo".to_string(),
+                product_name: Some("Coffee".to_string()),
+                counterparty: None,
+                product_identifier: Some("coffee".to_string()),
+                product_price: Some(150),
+                quantity: Some(2),
+                import_hash: None,
+                kind: TransactionKind::Purchase,
+            }],
+            deposits: 0,
+            consumption: 300,
+            closing_balance: 700,
+        }
+    }
+
+    #[test]
+    fn renders_text() {
+        assert_eq!(
+            "Statement for Anna, September 2026\n\
+             \n\
+             Opening balance: 10.-\n\
+             02.09.2026 08:15 - 2× Coffee (- 3.-)\n\
+             \n\
+             Deposits: 0.-\n\
+             Consumption: 3.-\n\
+             Closing balance: 7.-",
+            render_text(&statement(), &CurrencyFormatterImpl)
+        );
+    }
+
+    #[test]
+    fn renders_markdown() {
+        assert_eq!(
+            "*Statement for Anna, September 2026*\n\
+             Opening balance: 10.-\n\
+             - 02.09.2026 08:15 - 2× Coffee (- 3.-)\n\
+             *Deposits:* 0.-\n\
+             *Consumption:* 3.-\n\
+             *Closing balance:* 7.-",
+            render_markdown(&statement(), &CurrencyFormatterImpl)
+        );
+    }
+
+    #[test]
+    fn escapes_names_in_markdown() {
+        let mut statement = statement();
+        statement.user.name = "Anna_B*".to_string();
+        statement.transactions[0].product_name = Some("Caffè *Crema*".to_string());
+
+        let markdown = render_markdown(&statement, &CurrencyFormatterImpl);
+
+        assert!(markdown.starts_with("*Statement for Anna*\\_*B*\\**, September 2026*\n"));
+        assert!(markdown.contains("- 02.09.2026 08:15 - 2× Caffè \\*Crema\\* (- 3.-)\n"));
+    }
+
+    #[test]
+    fn renders_monthly_statements_of_all_users() {
+        let month = NaiveDate::from_ymd_opt(2026, 9, 1).unwrap();
+        let mut other_statement = statement();
+        other_statement.user.name = "Bert".to_string();
+        other_statement.transactions.clear();
+
+        let mut statement_service = StatementServiceMock::new();
+        statement_service
+            .expect_get_statements(|arg| arg.partial_eq(month))
+            .returns_once(Ok(vec![statement(), other_statement]));
+
+        let text =
+            render_monthly_statements(&statement_service, month, &CurrencyFormatterImpl).unwrap();
+
+        let statements = text.split(STATEMENT_SEPARATOR).collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                render_text(&statement(), &CurrencyFormatterImpl),
+                "Statement for Bert, September 2026\n\
+                 \n\
+                 Opening balance: 10.-\n\
+                 No transactions recorded\n\
+                 \n\
+                 Deposits: 0.-\n\
+                 Consumption: 3.-\n\
+                 Closing balance: 7.-"
+                    .to_string(),
+            ],
+            statements
+        );
+    }
+}
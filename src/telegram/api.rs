@@ -0,0 +1,169 @@
+use std::time::Duration;
+
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::error::Error;
+
+pub const DEFAULT_API_URL: &str = "https://api.telegram.org";
+
+/// How long a single `getUpdates` request waits for new messages.
+const POLL_TIMEOUT_SECONDS: u64 = 30;
+
+const MULTIPART_BOUNDARY: &str = "kafi-kaesseli-multipart-boundary";
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct Update {
+    pub update_id: i64,
+    pub message: Option<TelegramMessage>,
+}
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct TelegramMessage {
+    pub chat: Chat,
+    pub from: Option<TelegramUser>,
+    pub text: Option<String>,
+}
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct Chat {
+    pub id: i64,
+}
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct TelegramUser {
+    pub id: i64,
+    pub first_name: String,
+    pub last_name: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ApiResponse<T> {
+    ok: bool,
+    description: Option<String>,
+    result: Option<T>,
+}
+
+pub struct TelegramApi {
+    agent: ureq::Agent,
+    bot_url: String,
+}
+
+impl TelegramApi {
+    pub fn new(api_url: &str, token: &str) -> Self {
+        let agent = ureq::AgentBuilder::new()
+            .timeout_read(Duration::from_secs(POLL_TIMEOUT_SECONDS + 10))
+            .build();
+
+        Self {
+            agent,
+            bot_url: format!("{}/bot{}", api_url.trim_end_matches('/'), token),
+        }
+    }
+
+    pub fn get_updates(&self, offset: Option<i64>) -> Result<Vec<Update>, Error> {
+        self.call(
+            "getUpdates",
+            json!({
+                "offset": offset,
+                "timeout": POLL_TIMEOUT_SECONDS,
+                "allowed_updates": ["message"],
+            }),
+        )
+    }
+
+    pub fn send_message(&self, chat_id: i64, text: &str) -> Result<(), Error> {
+        self.call::<serde_json::Value>(
+            "sendMessage",
+            json!({
+                "chat_id": chat_id,
+                "text": text,
+                "parse_mode": "Markdown",
+            }),
+        )
+        .map(|_| ())
+    }
+
+    /// Uploads a photo, e.g. a QR code, with the given caption.
+    pub fn send_photo(
+        &self,
+        chat_id: i64,
+        caption: &str,
+        file_name: &str,
+        data: &[u8],
+    ) -> Result<(), Error> {
+        let mut body = Vec::new();
+        for (name, value) in &[
+            ("chat_id", chat_id.to_string()),
+            ("caption", caption.to_string()),
+            ("parse_mode", "Markdown".to_string()),
+        ] {
+            body.extend(
+                format!(
+                    "--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n{}\r\n",
+                    MULTIPART_BOUNDARY, name, value
+                )
+                .as_bytes(),
+            );
+        }
+        body.extend(
+            format!(
+                "--{}\r\nContent-Disposition: form-data; name=\"photo\"; filename=\"{}\"\r\n\
+                 Content-Type: application/octet-stream\r\n\r\n",
+                MULTIPART_BOUNDARY, file_name
+            )
+            .as_bytes(),
+        );
+        body.extend(data);
+        body.extend(format!("\r\n--{}--\r\n", MULTIPART_BOUNDARY).as_bytes());
+
+        let result = self
+            .request("sendPhoto")
+            .set(
+                "Content-Type",
+                &format!("multipart/form-data; boundary={}", MULTIPART_BOUNDARY),
+            )
+            .send_bytes(&body);
+
+        self.read_response::<serde_json::Value>("sendPhoto", result)
+            .map(|_| ())
+    }
+
+    fn call<T: DeserializeOwned>(&self, method: &str, body: serde_json::Value) -> Result<T, Error> {
+        let result = self.request(method).send_json(body);
+        self.read_response(method, result)
+    }
+
+    fn request(&self, method: &str) -> ureq::Request {
+        self.agent.post(&format!("{}/{}", self.bot_url, method))
+    }
+
+    fn read_response<T: DeserializeOwned>(
+        &self,
+        method: &str,
+        result: Result<ureq::Response, ureq::Error>,
+    ) -> Result<T, Error> {
+        let response = match result {
+            Ok(response) | Err(ureq::Error::Status(_, response)) => response,
+            Err(error) => return Err(Error::Network(error.to_string())),
+        };
+
+        let response = response
+            .into_json::<ApiResponse<T>>()
+            .map_err(|error| Error::Network(error.to_string()))?;
+
+        match response {
+            ApiResponse {
+                ok: true,
+                result: Some(result),
+                ..
+            } => Ok(result),
+            ApiResponse { description, .. } => Err(Error::Network(format!(
+                "{} failed: {}",
+                method,
+                description.unwrap_or_else(|| "unknown error".to_string())
+            ))),
+        }
+    }
+}
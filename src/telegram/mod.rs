@@ -0,0 +1,293 @@
+use std::thread;
+use std::time::Duration;
+
+use api::{TelegramApi, TelegramMessage, Update};
+
+use crate::error::Error;
+use crate::message_handler::MessageHandler;
+use crate::models::{Message, User};
+
+pub mod api;
+
+/// How long to wait before polling again after a failed request.
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+pub struct TelegramBot<'a> {
+    api: TelegramApi,
+    message_handler: Box<dyn MessageHandler + 'a>,
+    offset: Option<i64>,
+}
+
+impl<'a> TelegramBot<'a> {
+    pub fn new(api: TelegramApi, message_handler: Box<dyn MessageHandler + 'a>) -> Self {
+        Self {
+            api,
+            message_handler,
+            offset: None,
+        }
+    }
+
+    pub fn run(&mut self) -> ! {
+        loop {
+            if let Err(error) = self.poll() {
+                log::error!("Failed to poll Telegram updates: {}", error);
+                thread::sleep(RETRY_DELAY);
+            }
+        }
+    }
+
+    /// Fetches one batch of updates and answers every text message in it.
+    pub fn poll(&mut self) -> Result<(), Error> {
+        for update in self.api.get_updates(self.offset)? {
+            self.offset = Some(update.update_id + 1);
+            self.handle_update(&update)?;
+        }
+
+        Ok(())
+    }
+
+    fn handle_update(&self, update: &Update) -> Result<(), Error> {
+        let telegram_message = match &update.message {
+            Some(telegram_message) => telegram_message,
+            None => return Ok(()),
+        };
+
+        let message = match to_message(telegram_message) {
+            Some(message) => message,
+            None => return Ok(()),
+        };
+
+        for response in self.message_handler.handle_message(&message) {
+            match &response.attachment {
+                Some(attachment) => self.api.send_photo(
+                    telegram_message.chat.id,
+                    &response.contents,
+                    &attachment.file_name,
+                    &attachment.data,
+                )?,
+                None => self
+                    .api
+                    .send_message(telegram_message.chat.id, &response.contents)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn to_message(telegram_message: &TelegramMessage) -> Option<Message> {
+    let sender = telegram_message.from.as_ref()?;
+    let text = telegram_message.text.as_ref()?;
+
+    let name = match &sender.last_name {
+        Some(last_name) => format!("{} {}", sender.first_name, last_name),
+        None => sender.first_name.clone(),
+    };
+
+    Some(Message {
+        sender: User {
+            id: sender.id.to_string(),
+            name,
+        },
+        contents: strip_bot_mention(text),
+    })
+}
+
+/// Commands in group chats are sent as `/list@SomeBot`.
+fn strip_bot_mention(text: &str) -> String {
+    match text.split_once(' ') {
+        Some((command, arguments)) if command.starts_with('/') => format!(
+            "{} {}",
+            command.split('@').next().unwrap_or(command),
+            arguments
+        ),
+        None if text.starts_with('/') => text.split('@').next().unwrap_or(text).to_string(),
+        _ => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+    use std::sync::mpsc::{channel, Receiver};
+
+    use serde_json::{json, Value};
+    use tiny_http::{Header, Response as HttpResponse, Server};
+
+    use crate::message_handler::MessageHandlerMock;
+    use crate::models::{Attachment, Response};
+
+    use super::*;
+
+    /// Serves the given JSON bodies in order and reports each received request.
+    /// Request bodies that aren't JSON are reported as strings.
+    fn start_mock_server(responses: Vec<Value>) -> (String, Receiver<(String, Value)>) {
+        let server = Server::http("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", server.server_addr());
+        let (sender, receiver) = channel();
+
+        thread::spawn(move || {
+            for response in responses {
+                let mut request = server.recv().unwrap();
+
+                let mut body = Vec::new();
+                request.as_reader().read_to_end(&mut body).unwrap();
+                let body = serde_json::from_slice(&body)
+                    .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(&body).into_owned()));
+                sender.send((request.url().to_string(), body)).unwrap();
+
+                let header = Header::from_bytes("Content-Type", "application/json").unwrap();
+                request
+                    .respond(HttpResponse::from_string(response.to_string()).with_header(header))
+                    .unwrap();
+            }
+        });
+
+        (url, receiver)
+    }
+
+    #[test]
+    fn answers_text_messages() {
+        let (url, requests) = start_mock_server(vec![
+            json!({
+                "ok": true,
+                "result": [{
+                    "update_id": 41,
+                    "message": {
+                        "chat": { "id": 7 },
+                        "from": { "id": 1234, "first_name": "Anna", "last_name": "Muster" },
+                        "text": "/list@KafiBot"
+                    }
+                }]
+            }),
+            json!({ "ok": true, "result": {} }),
+        ]);
+
+        let mut message_handler = MessageHandlerMock::new();
+        message_handler
+            .expect_handle_message(|arg| {
+                arg.partial_eq_owned(Message {
+                    sender: User {
+                        id: "1234".to_string(),
+                        name: "Anna Muster".to_string(),
+                    },
+                    contents: "/list".to_string(),
+                })
+            })
+            .returns_once(vec![Response {
+                contents: "Available products:".to_string(),
+                attachment: None,
+            }]);
+
+        let mut bot = TelegramBot::new(TelegramApi::new(&url, "token"), Box::new(message_handler));
+        bot.poll().unwrap();
+
+        let (path, body) = requests.recv().unwrap();
+        assert_eq!("/bottoken/getUpdates", path);
+        assert_eq!(Value::Null, body["offset"]);
+
+        let (path, body) = requests.recv().unwrap();
+        assert_eq!("/bottoken/sendMessage", path);
+        assert_eq!(
+            json!({
+                "chat_id": 7,
+                "text": "Available products:",
+                "parse_mode": "Markdown",
+            }),
+            body
+        );
+
+        assert_eq!(Some(42), bot.offset);
+    }
+
+    #[test]
+    fn sends_attachments_as_photos() {
+        let (url, requests) = start_mock_server(vec![
+            json!({
+                "ok": true,
+                "result": [{
+                    "update_id": 8,
+                    "message": {
+                        "chat": { "id": 7 },
+                        "from": { "id": 1234, "first_name": "Anna" },
+                        "text": "/topup"
+                    }
+                }]
+            }),
+            json!({ "ok": true, "result": {} }),
+        ]);
+
+        let mut message_handler = MessageHandlerMock::new();
+        message_handler
+            .expect_handle_message(|arg| arg.any())
+            .returns_once(vec![Response {
+                contents: "Please pay 12.50".to_string(),
+                attachment: Some(Attachment {
+                    file_name: "topup.png".to_string(),
+                    data: b"image".to_vec(),
+                }),
+            }]);
+
+        let mut bot = TelegramBot::new(TelegramApi::new(&url, "token"), Box::new(message_handler));
+        bot.poll().unwrap();
+
+        requests.recv().unwrap();
+        let (path, body) = requests.recv().unwrap();
+        assert_eq!("/bottoken/sendPhoto", path);
+
+        let body = body.as_str().unwrap();
+        assert!(body.contains("name=\"caption\"\r\n\r\nPlease pay 12.50\r\n"));
+        assert!(body.contains("filename=\"topup.png\""));
+        assert!(body.contains("\r\n\r\nimage\r\n"));
+    }
+
+    #[test]
+    fn ignores_updates_without_text() {
+        let (url, requests) = start_mock_server(vec![json!({
+            "ok": true,
+            "result": [{
+                "update_id": 3,
+                "message": {
+                    "chat": { "id": 7 },
+                    "from": { "id": 1234, "first_name": "Anna" }
+                }
+            }]
+        })]);
+
+        let mut bot = TelegramBot::new(
+            TelegramApi::new(&url, "token"),
+            Box::new(MessageHandlerMock::new()),
+        );
+        bot.poll().unwrap();
+
+        requests.recv().unwrap();
+        assert_eq!(Some(4), bot.offset);
+    }
+
+    #[test]
+    fn reports_api_errors() {
+        let (url, _requests) = start_mock_server(vec![json!({
+            "ok": false,
+            "description": "Unauthorized"
+        })]);
+
+        let mut bot = TelegramBot::new(
+            TelegramApi::new(&url, "token"),
+            Box::new(MessageHandlerMock::new()),
+        );
+
+        assert_eq!(
+            Err(Error::Network(
+                "getUpdates failed: Unauthorized".to_string()
+            )),
+            bot.poll()
+        );
+    }
+
+    #[test]
+    fn strips_bot_mention_from_commands() {
+        assert_eq!("/history 3", strip_bot_mention("/history@KafiBot 3"));
+        assert_eq!("1.20", strip_bot_mention("1.20"));
+        assert_eq!("/pay @anna 2", strip_bot_mention("/pay @anna 2"));
+    }
+}